@@ -0,0 +1,59 @@
+use std::time::Instant;
+
+use gbmu::cartridge::mbc0::MBC0;
+use gbmu::cpu::CPU;
+use gbmu::mmu::MMU;
+use gbmu::model::Model;
+
+const ROM_SIZE: usize = 0x8000;
+const INSTRUCTIONS: u64 = 20_000_000;
+
+/// A loop mixing loads, ALU, CB-prefixed, stack and control flow
+/// instructions, with (HL) pointing into WRAM.
+const PROGRAM: [u8; 35] = [
+    0x31, 0xFE, 0xDF, // 0000: LD SP,$DFFE
+    0x21, 0x00, 0xC0, // 0003: LD HL,$C000
+    0x06, 0x10, //       0006: LD B,$10
+    0x78, //             0008: LD A,B
+    0x80, //             0009: ADD A,B
+    0x3C, //             000A: INC A
+    0x77, //             000B: LD [HL],A
+    0x86, //             000C: ADD A,[HL]
+    0xCB, 0x37, //       000D: SWAP A
+    0xCB, 0x46, //       000F: BIT 0,[HL]
+    0xC5, //             0011: PUSH BC
+    0xC1, //             0012: POP BC
+    0xCD, 0x20, 0x00, // 0013: CALL $0020
+    0xA8, //             0016: XOR A,B
+    0xE6, 0x0F, //       0017: AND A,$0F
+    0x05, //             0019: DEC B
+    0x20, 0xEC, //       001A: JR NZ,$0008
+    0xC3, 0x03, 0x00, // 001C: JP $0003
+    0x00, //             001F: NOP
+    0x23, //             0020: INC HL
+    0x2B, //             0021: DEC HL
+    0xC9, //             0022: RET
+];
+
+fn main() {
+    let mut rom = vec![0; ROM_SIZE];
+    rom[..PROGRAM.len()].copy_from_slice(&PROGRAM);
+    let mut mbc = MBC0::new(rom, 0);
+    let mut mmu = MMU::new(&mut mbc, Model::DMG);
+    let mut cpu = CPU::new();
+
+    let start = Instant::now();
+    let mut cycles: u64 = 0;
+    for _ in 0..INSTRUCTIONS {
+        cycles += cpu.run(&mut mmu).expect("benchmark program failed") as u64;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    println!(
+        "dispatch: {} instructions, {} M-cycles in {:.3}s ({:.2} M instructions/s)",
+        INSTRUCTIONS,
+        cycles,
+        elapsed,
+        INSTRUCTIONS as f64 / elapsed / 1_000_000.0
+    );
+}
@@ -0,0 +1,75 @@
+pub const APU_START: usize = 0xFF10;
+pub const NR52_REGISTER: usize = 0xFF26;
+pub const APU_END: usize = 0xFF2F;
+pub const WAVE_RAM_START: usize = 0xFF30;
+pub const WAVE_RAM_END: usize = 0xFF3F;
+
+const NR52_POWER: u8 = 0b10000000;
+const NR52_UNUSED_BITS: u8 = 0b01110000;
+const REGISTER_COUNT: usize = APU_END - APU_START + 1;
+const WAVE_RAM_SIZE: usize = WAVE_RAM_END - WAVE_RAM_START + 1;
+
+/// Bits reading as 1 for 0xFF10-0xFF2F, whether unused or write-only.
+const READ_MASKS: [u8; REGISTER_COUNT] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR20-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR40-NR44
+    0x00, 0x00, 0x70, // NR50-NR52
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
+
+/// Sound registers and wave RAM. No channel is synthesized yet, so the
+/// channel status bits of NR52 read as off.
+#[derive(Debug)]
+pub struct APU {
+    registers: [u8; REGISTER_COUNT],
+    wave_ram: [u8; WAVE_RAM_SIZE],
+}
+
+impl Default for APU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl APU {
+    pub fn new() -> Self {
+        APU {
+            registers: [0; REGISTER_COUNT],
+            wave_ram: [0; WAVE_RAM_SIZE],
+        }
+    }
+
+    pub fn read(&self, address: usize) -> u8 {
+        match address {
+            NR52_REGISTER => self.registers[NR52_REGISTER - APU_START] | NR52_UNUSED_BITS,
+            APU_START..=APU_END => {
+                let index = address - APU_START;
+                self.registers[index] | READ_MASKS[index]
+            }
+            WAVE_RAM_START..=WAVE_RAM_END => self.wave_ram[address - WAVE_RAM_START],
+            _ => 0xFF,
+        }
+    }
+
+    /// While powered off through NR52, every register but NR52 ignores
+    /// writes; powering off clears them.
+    pub fn write(&mut self, address: usize, value: u8) {
+        match address {
+            NR52_REGISTER => {
+                if value & NR52_POWER == 0 {
+                    self.registers = [0; REGISTER_COUNT];
+                }
+                self.registers[NR52_REGISTER - APU_START] = value & NR52_POWER;
+            }
+            APU_START..=APU_END if self.powered() => self.registers[address - APU_START] = value,
+            WAVE_RAM_START..=WAVE_RAM_END => self.wave_ram[address - WAVE_RAM_START] = value,
+            _ => {}
+        }
+    }
+
+    fn powered(&self) -> bool {
+        self.registers[NR52_REGISTER - APU_START] & NR52_POWER != 0
+    }
+}
@@ -0,0 +1,250 @@
+use crate::apu::NR52_REGISTER;
+use crate::bus::{Bus, IE_REGISTER, IF_REGISTER};
+use crate::cartridge::header::CartridgeHeader;
+use crate::cpu::CPU;
+use crate::error::EmuError;
+use crate::joypad::P1_REGISTER;
+use crate::mmu::io::{DMA_REGISTER, KEY0_DMG_COMPATIBILITY, KEY0_REGISTER};
+use crate::mmu::MMU;
+use crate::model::Model;
+use crate::ppu::{BGP_REGISTER, LCDC_REGISTER, LY_REGISTER, STAT_REGISTER};
+use crate::serial::{SB_REGISTER, SC_REGISTER};
+use crate::timer::{DIV_REGISTER, TAC_REGISTER, TIMA_REGISTER, TMA_REGISTER};
+
+pub const ENTRY_POINT: u16 = 0x0100;
+const STACK_POINTER: u16 = 0xFFFE;
+const CGB_FLAG_SUPPORTED: u8 = 0x80;
+
+/// IO registers as left by every boot ROM, before the per-model adjustments
+/// of `post_boot_io`. The APU is powered on first so the sound registers
+/// accept their values.
+const IO_STATE: [(usize, u8); 31] = [
+    (NR52_REGISTER, 0x80),
+    (P1_REGISTER, 0xCF),
+    (SB_REGISTER, 0x00),
+    (SC_REGISTER, 0x7E),
+    (TIMA_REGISTER, 0x00),
+    (TMA_REGISTER, 0x00),
+    (TAC_REGISTER, 0xF8),
+    (IF_REGISTER, 0xE1),
+    (0xFF10, 0x80), // NR10
+    (0xFF11, 0xBF), // NR11
+    (0xFF12, 0xF3), // NR12
+    (0xFF13, 0xFF), // NR13
+    (0xFF14, 0xBF), // NR14
+    (0xFF16, 0x3F), // NR21
+    (0xFF17, 0x00), // NR22
+    (0xFF18, 0xFF), // NR23
+    (0xFF19, 0xBF), // NR24
+    (0xFF1A, 0x7F), // NR30
+    (0xFF1B, 0xFF), // NR31
+    (0xFF1C, 0x9F), // NR32
+    (0xFF1D, 0xFF), // NR33
+    (0xFF1E, 0xBF), // NR34
+    (0xFF20, 0xFF), // NR41
+    (0xFF21, 0x00), // NR42
+    (0xFF22, 0x00), // NR43
+    (0xFF23, 0xBF), // NR44
+    (0xFF24, 0x77), // NR50
+    (0xFF25, 0xF3), // NR51
+    (LCDC_REGISTER, 0x91),
+    (BGP_REGISTER, 0xFC),
+    (IE_REGISTER, 0x00),
+];
+
+const LOGO_TILES_START: u16 = 0x8010;
+const REGISTERED_TILE_START: u16 = 0x8190;
+const REGISTERED_TILE: [u8; 8] = [0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C];
+const REGISTERED_TILE_INDEX: u8 = 0x19;
+const LOGO_TILES_PER_ROW: u8 = 12;
+const TILE_MAP_LOGO_TOP: u16 = 0x9904;
+const TILE_MAP_LOGO_BOTTOM: u16 = 0x9924;
+const TILE_MAP_REGISTERED: u16 = 0x9910;
+
+/// Puts the machine in the state the boot ROM of `model` leaves it in when
+/// jumping to the cartridge entry point, so games can start without one.
+pub fn skip_boot_rom(
+    model: Model,
+    header: &CartridgeHeader,
+    cpu: &mut CPU,
+    mmu: &mut MMU,
+) -> Result<(), EmuError> {
+    let [af, bc, de, hl] = post_boot_registers(model, header);
+    let registers = &mut cpu.registers;
+    registers.set_af(af);
+    registers.set_bc(bc);
+    registers.set_de(de);
+    registers.set_hl(hl);
+    registers.sp = STACK_POINTER;
+    registers.pc = ENTRY_POINT;
+    cpu.set_ime(false);
+
+    if model.is_cgb() {
+        mmu.set_io_state(KEY0_REGISTER, post_boot_key0(header))?;
+    }
+    for (address, value) in post_boot_io(model) {
+        mmu.set_io_state(address, value)?;
+    }
+    if !model.is_cgb() {
+        write_logo(header, mmu)?;
+    }
+    Ok(())
+}
+
+/// AF, BC, DE and HL after the boot ROM. DMG flags reflect the header
+/// checksum verification; CGB running an older cartridge leaves the
+/// compatibility palette lookup (title checksum) in B and HL.
+pub fn post_boot_registers(model: Model, header: &CartridgeHeader) -> [u16; 4] {
+    let cgb_mode = header.cgb_flag() & CGB_FLAG_SUPPORTED != 0;
+    let checksum_flags = match header.header_checksum[0] {
+        0x00 => 0x80,
+        _ => 0xB0,
+    };
+    let title_checksum = match header.nintendo_licensee() {
+        true => header.title_checksum(),
+        false => 0x00,
+    };
+    let compatibility_hl = match title_checksum {
+        0x43 | 0x58 => 0x991A,
+        _ => 0x007C,
+    };
+
+    match model {
+        Model::DMG0 => [0x0100, 0xFF13, 0x00C1, 0x8403],
+        Model::DMG => [0x0100 | checksum_flags, 0x0013, 0x00D8, 0x014D],
+        Model::MGB => [0xFF00 | checksum_flags, 0x0013, 0x00D8, 0x014D],
+        Model::SGB => [0x0100, 0x0014, 0x0000, 0xC060],
+        Model::SGB2 => [0xFF00, 0x0014, 0x0000, 0xC060],
+        Model::CGB0 | Model::CGB | Model::Stadium2 if cgb_mode => [0x1180, 0x0000, 0xFF56, 0x000D],
+        Model::CGB0 | Model::CGB | Model::Stadium2 => [
+            0x1180,
+            (title_checksum as u16) << 8,
+            0x0008,
+            compatibility_hl,
+        ],
+        // The AGB boot ROM ends with an extra `INC B`, visible in Z and H.
+        Model::AGB0 | Model::AGB => {
+            let b = if cgb_mode { 0 } else { title_checksum }.wrapping_add(1);
+            let z = ((b == 0) as u16) << 7;
+            let h = ((b & 0x0F == 0) as u16) << 5;
+            match cgb_mode {
+                true => [0x1100 | z | h, (b as u16) << 8, 0xFF56, 0x000D],
+                false => [0x1100 | z | h, (b as u16) << 8, 0x0008, compatibility_hl],
+            }
+        }
+    }
+}
+
+/// The CGB boot ROM copies the header CGB flag to KEY0, or selects DMG
+/// compatibility mode for cartridges without CGB support.
+pub fn post_boot_key0(header: &CartridgeHeader) -> u8 {
+    match header.cgb_flag() & CGB_FLAG_SUPPORTED != 0 {
+        true => header.cgb_flag(),
+        false => KEY0_DMG_COMPATIBILITY,
+    }
+}
+
+/// IO registers after the boot ROM. DIV depends on how long the boot ROM
+/// ran; the SGB and CGB values vary with the cartridge and are left at 0.
+pub fn post_boot_io(model: Model) -> Vec<(usize, u8)> {
+    let mut io = IO_STATE.to_vec();
+    let (div, stat, ly) = match model {
+        Model::DMG0 => (0x18, 0x81, 0x91),
+        Model::DMG | Model::MGB => (0xAB, 0x85, 0x00),
+        _ => (0x00, 0x85, 0x00),
+    };
+    io.push((DIV_REGISTER, div));
+    io.push((STAT_REGISTER, stat));
+    io.push((LY_REGISTER, ly));
+    // Except on SGB, the boot sound leaves channel 1 on and NR52 at 0xF1.
+    // Channel status isn't modelled, so NR52 keeps the 0xF0 that the
+    // power-on write of `IO_STATE` reads back as.
+    io.push((DMA_REGISTER, if model.is_cgb() { 0x00 } else { 0xFF }));
+    if model.is_cgb() {
+        io.push((SC_REGISTER, 0x7F));
+    }
+    io
+}
+
+/// Decompresses the header logo into VRAM the way the DMG boot ROM does:
+/// every bit is doubled horizontally and every row vertically, followed by
+/// the ® tile, and places both on the background tile map.
+fn write_logo(header: &CartridgeHeader, mmu: &mut MMU) -> Result<(), EmuError> {
+    let mut address = LOGO_TILES_START;
+    for byte in header.nintendo_logo {
+        for nibble in [byte >> 4, byte & 0x0F] {
+            let row = (0..4).fold(0u8, |row, bit| {
+                row | (((nibble >> bit) & 1) * (0b11 << (bit * 2)))
+            });
+            for _ in 0..2 {
+                mmu.set_word(address as usize, row)?;
+                address += 2;
+            }
+        }
+    }
+    for (row, byte) in REGISTERED_TILE.iter().enumerate() {
+        mmu.set_word(REGISTERED_TILE_START as usize + row * 2, *byte)?;
+    }
+
+    for tile in 0..LOGO_TILES_PER_ROW {
+        mmu.set_word((TILE_MAP_LOGO_TOP + tile as u16) as usize, tile + 1)?;
+        mmu.set_word(
+            (TILE_MAP_LOGO_BOTTOM + tile as u16) as usize,
+            tile + 1 + LOGO_TILES_PER_ROW,
+        )?;
+    }
+    mmu.set_word(TILE_MAP_REGISTERED as usize, REGISTERED_TILE_INDEX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::header::{
+        HEADER_CHECKSUM_OFFSET, HEADER_END, HEADER_OFFSET, OLD_LICENSEE_CODE_OFFSET, TITLE_OFFSET,
+    };
+
+    const NINTENDO: u8 = 0x01;
+    const OTHER_LICENSEE: u8 = 0x00;
+
+    fn header(title: &[u8], licensee: u8, header_checksum: u8) -> CartridgeHeader {
+        let mut bytes = vec![0; HEADER_END - HEADER_OFFSET + 1];
+        let title_start = TITLE_OFFSET as usize;
+        bytes[title_start..title_start + title.len()].copy_from_slice(title);
+        bytes[OLD_LICENSEE_CODE_OFFSET as usize] = licensee;
+        bytes[HEADER_CHECKSUM_OFFSET as usize] = header_checksum;
+        CartridgeHeader::from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn dmg_half_carry_and_carry_follow_the_header_checksum() {
+        let [af, ..] = post_boot_registers(Model::DMG, &header(b"", NINTENDO, 0x00));
+        assert_eq!(af, 0x0180);
+        let [af, ..] = post_boot_registers(Model::DMG, &header(b"", NINTENDO, 0x42));
+        assert_eq!(af, 0x01B0);
+        let [af, ..] = post_boot_registers(Model::MGB, &header(b"", NINTENDO, 0x42));
+        assert_eq!(af, 0xFFB0);
+    }
+
+    #[test]
+    fn cgb_leaves_the_title_checksum_of_dmg_cartridges() {
+        // "C" sums to 0x43, one of the titles HL points to a palette for.
+        let registers = post_boot_registers(Model::CGB, &header(b"C", NINTENDO, 0x00));
+        assert_eq!(registers, [0x1180, 0x4300, 0x0008, 0x991A]);
+        let registers = post_boot_registers(Model::CGB, &header(b"AB", NINTENDO, 0x00));
+        assert_eq!(registers, [0x1180, 0x8300, 0x0008, 0x007C]);
+    }
+
+    #[test]
+    fn cgb_ignores_the_title_of_other_licensees() {
+        let registers = post_boot_registers(Model::CGB, &header(b"C", OTHER_LICENSEE, 0x00));
+        assert_eq!(registers, [0x1180, 0x0000, 0x0008, 0x007C]);
+    }
+
+    #[test]
+    fn agb_increments_b() {
+        let [af, bc, ..] = post_boot_registers(Model::AGB, &header(b"C", NINTENDO, 0x00));
+        assert_eq!((af, bc), (0x1100, 0x4400));
+        let [af, bc, ..] = post_boot_registers(Model::AGB, &header(b"", OTHER_LICENSEE, 0x00));
+        assert_eq!((af, bc), (0x1100, 0x0100));
+    }
+}
@@ -0,0 +1,89 @@
+use crate::error::EmuError;
+
+use crate::interrupts::{self, Interrupt};
+
+pub const IF_REGISTER: usize = 0xFF0F;
+pub const IE_REGISTER: usize = 0xFFFF;
+
+/// Memory as seen by the CPU.
+///
+/// Only byte accesses are required. Interrupt state defaults to plain reads
+/// and writes of IF and IE, so a flat 64 KiB array is a complete bus;
+/// hardware implementations such as `MMU` override these with their own
+/// controllers.
+pub trait Bus {
+    fn get_word(&mut self, address: usize) -> Result<u8, EmuError>;
+
+    fn set_word(&mut self, address: usize, value: u8) -> Result<(), EmuError>;
+
+    fn get_dword(&mut self, address: usize) -> Result<u16, EmuError> {
+        let mut dword = self.get_word(address)? as u16;
+        dword |= (self.get_word((address + 1) & 0xFFFF)? as u16) << 8;
+        Ok(dword)
+    }
+
+    fn set_dword(&mut self, address: usize, value: u16) -> Result<(), EmuError> {
+        self.set_word(address, (value & 0x00FF) as u8)?;
+        self.set_word((address + 1) & 0xFFFF, (value >> 8) as u8)?;
+        Ok(())
+    }
+
+    /// Reads a byte for diagnostics, without the side effects or access
+    /// restrictions a CPU read would have. `None` when nothing is mapped.
+    fn peek(&mut self, address: usize) -> Option<u8> {
+        self.get_word(address).ok()
+    }
+
+    /// Called by the CPU after each step with the M-cycles it consumed.
+    fn tick(&mut self, _cycles: u8) {}
+
+    /// Called by the CPU before `tick` with whether it is in HALT, for the
+    /// hardware that pauses meanwhile.
+    fn set_cpu_halted(&mut self, _halted: bool) {}
+
+    fn pending_interrupt(&mut self) -> Result<Option<Interrupt>, EmuError> {
+        let pending = self.get_word(IF_REGISTER)? & self.get_word(IE_REGISTER)?;
+        Ok(interrupts::highest_priority(pending))
+    }
+
+    fn interrupt_requested(&mut self, interrupt: Interrupt) -> Result<bool, EmuError> {
+        Ok(self.get_word(IF_REGISTER)? & interrupt.mask() != 0)
+    }
+
+    fn acknowledge_interrupt(&mut self, interrupt: Interrupt) -> Result<(), EmuError> {
+        let flag = self.get_word(IF_REGISTER)?;
+        self.set_word(IF_REGISTER, flag & !interrupt.mask())
+    }
+
+    /// Performs a pending CGB speed switch, returning whether one happened.
+    fn switch_speed(&mut self) -> bool {
+        false
+    }
+}
+
+/// 64 KiB of plain memory with a program loaded, for CPU tests.
+#[cfg(test)]
+pub struct FlatBus {
+    pub memory: Box<[u8]>,
+}
+
+#[cfg(test)]
+impl FlatBus {
+    pub fn with_program(address: u16, program: &[u8]) -> Self {
+        let mut memory = vec![0; 0x10000].into_boxed_slice();
+        memory[address as usize..address as usize + program.len()].copy_from_slice(program);
+        FlatBus { memory }
+    }
+}
+
+#[cfg(test)]
+impl Bus for FlatBus {
+    fn get_word(&mut self, address: usize) -> Result<u8, EmuError> {
+        Ok(self.memory[address])
+    }
+
+    fn set_word(&mut self, address: usize, value: u8) -> Result<(), EmuError> {
+        self.memory[address] = value;
+        Ok(())
+    }
+}
@@ -1,53 +1,223 @@
 pub mod header;
 pub mod mbc0;
+pub mod mbc1;
+pub mod mbc2;
+pub mod mbc3;
+pub mod rtc;
 
-use std::io;
-use std::io::Read;
-use std::ptr;
+use std::fmt;
+use std::io::{self, Read};
+use std::mem;
+use std::path::{Path, PathBuf};
 
 use crate::cartridge::header::CartridgeHeader;
 use crate::cartridge::header::{HEADER_END, HEADER_OFFSET};
 use crate::cartridge::mbc0::MBC0;
-use crate::error;
+use crate::cartridge::mbc1::MBC1;
+use crate::cartridge::mbc2::MBC2;
+use crate::cartridge::mbc3::MBC3;
+use crate::cartridge::rtc::Rtc;
+use crate::clock::{Clock, SystemClock};
+use crate::error::{self, EmuError};
+
+/// The controller between the cartridge ROM/RAM and the bus. Addresses are
+/// relative to the start of each area: 0x0000-0x7FFF for ROM and
+/// 0xA000-0xBFFF for RAM.
+pub trait MemoryBankController: fmt::Debug {
+    fn rom_read(&self, address: usize) -> u8;
+
+    /// Writes to ROM addresses set the controller registers.
+    fn rom_write(&mut self, address: usize, value: u8);
+
+    fn ram_read(&self, address: usize) -> u8;
+
+    fn ram_write(&mut self, address: usize, value: u8);
+
+    /// Bank mapped at 0x4000-0x7FFF.
+    fn rom_bank(&self) -> usize {
+        1
+    }
+
+    /// Advances anything the cartridge clocks on its own, like a real-time
+    /// clock, by the M-cycles the CPU just consumed.
+    fn tick(&mut self, _cycles: u8, _double_speed: bool) {}
+
+    /// State kept by the battery, written to the save file.
+    fn save_data(&self) -> Vec<u8>;
+
+    /// Restores the state returned by `save_data`. Missing bytes are left
+    /// as they are and extra bytes ignored.
+    fn load_save_data(&mut self, data: &[u8]);
+
+    /// Whether RAM was written since the last call and is disabled again,
+    /// the way games close their saves: a good time to write the save file.
+    fn take_flush_request(&mut self) -> bool {
+        false
+    }
+}
+
+/// Size of the switchable ROM banks, which every controller maps at
+/// 0x4000-0x7FFF.
+const ROM_BANK_SIZE: usize = 0x4000;
+/// RAM is enabled by writing 0x0A to the low nibble of the enable register;
+/// anything else disables it.
+const RAM_ENABLE_MASK: u8 = 0x0F;
+const RAM_ENABLE_VALUE: u8 = 0x0A;
+
+/// Whether a write to the RAM enable register enables RAM.
+fn enables_ram(value: u8) -> bool {
+    value & RAM_ENABLE_MASK == RAM_ENABLE_VALUE
+}
+
+/// Banks in a ROM of `rom_size` bytes, the size declared by the header,
+/// which bank numbers wrap to.
+fn rom_bank_count(rom_size: usize) -> usize {
+    (rom_size / ROM_BANK_SIZE).max(2)
+}
+
+/// Copies the RAM part of a save into `ram`, leaving missing bytes as they
+/// are. Returns the bytes copied; anything after them belongs to whatever
+/// the controller saves next.
+fn load_ram(ram: &mut [u8], data: &[u8]) -> usize {
+    let length = data.len().min(ram.len());
+    ram[..length].copy_from_slice(&data[..length]);
+    length
+}
+
+/// Whether battery-backed state was written since the last flush request,
+/// for controllers to implement `take_flush_request`.
+#[derive(Debug, Default)]
+struct RamWrites {
+    pending: bool,
+}
+
+impl RamWrites {
+    fn record(&mut self) {
+        self.pending = true;
+    }
+
+    /// Requests a flush once RAM is disabled after a write.
+    fn take_flush_request(&mut self, ram_enabled: bool) -> bool {
+        !ram_enabled && mem::take(&mut self.pending)
+    }
+}
+
+/// Extension of the save file written next to the ROM.
+const SAVE_EXTENSION: &str = "sav";
+/// Saves are written here first, then renamed over the save file, so an
+/// interrupted write can't corrupt it.
+const SAVE_TEMPORARY_EXTENSION: &str = "sav.tmp";
+
+/// Where a cartridge with a battery persists its state.
+#[derive(Debug, Clone)]
+pub struct SaveFile {
+    path: PathBuf,
+}
+
+impl SaveFile {
+    /// The save file next to the ROM at `rom_path`.
+    pub fn for_rom(rom_path: &str) -> Self {
+        SaveFile {
+            path: Path::new(rom_path).with_extension(SAVE_EXTENSION),
+        }
+    }
+
+    /// Restores the battery-backed state of `mbc`, if the file exists.
+    pub fn read(&self, mbc: &mut dyn MemoryBankController) -> Result<(), EmuError> {
+        match std::fs::read(&self.path) {
+            Ok(data) => mbc.load_save_data(&data),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error.into()),
+        }
+        Ok(())
+    }
+
+    pub fn write(&self, mbc: &dyn MemoryBankController) -> Result<(), EmuError> {
+        let temporary = self.path.with_extension(SAVE_TEMPORARY_EXTENSION);
+        std::fs::write(&temporary, mbc.save_data())?;
+        std::fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+}
 
 #[derive(Debug)]
 pub struct Cartridge {
     pub header: CartridgeHeader,
-    pub mbc: MBC0,
+    pub mbc: Box<dyn MemoryBankController>,
+    /// Where battery-backed state is persisted, if the cartridge has one.
+    save_file: Option<SaveFile>,
 }
 
 impl Cartridge {
-    pub fn load_mbc(cartridge_type: u8, data: Vec<u8>) -> MBC0 {
-        match cartridge_type {
-            0x00 => MBC0::new(data),
-            _ => MBC0::new(data),
+    /// Picks the controller from the header cartridge type byte. `clock`
+    /// is used by controllers with a real-time clock.
+    pub fn load_mbc(
+        header: &CartridgeHeader,
+        data: Vec<u8>,
+        clock: Box<dyn Clock>,
+    ) -> Result<Box<dyn MemoryBankController>, EmuError> {
+        let ram_size = header.ram_size();
+        match header.cartridge_type[0] {
+            // ROM ONLY, ROM+RAM, ROM+RAM+BATTERY
+            0x00 | 0x08 | 0x09 => Ok(Box::new(MBC0::new(data, ram_size))),
+            // MBC1, MBC1+RAM, MBC1+RAM+BATTERY
+            0x01..=0x03 => Ok(Box::new(MBC1::new(data, header.rom_size(), ram_size))),
+            // MBC2, MBC2+BATTERY
+            0x05 | 0x06 => Ok(Box::new(MBC2::new(data, header.rom_size()))),
+            // MBC3+TIMER+BATTERY, MBC3+TIMER+RAM+BATTERY
+            0x0F | 0x10 => {
+                let rtc = Some(Rtc::new(clock));
+                Ok(Box::new(MBC3::new(data, header.rom_size(), ram_size, rtc)))
+            }
+            // MBC3, MBC3+RAM, MBC3+RAM+BATTERY
+            0x11..=0x13 => Ok(Box::new(MBC3::new(data, header.rom_size(), ram_size, None))),
+            cartridge_type => Err(EmuError::UnsupportedCartridge { cartridge_type }),
         }
     }
 
-    pub fn load_rom(rom_path: &str) -> Result<Cartridge, std::io::Error> {
+    pub fn load_rom(rom_path: &str) -> Result<Cartridge, EmuError> {
+        Self::load_rom_with_clock(rom_path, Box::new(SystemClock))
+    }
+
+    /// Loads a ROM whose real-time clock, if any, takes the current time
+    /// from `clock` instead of the system.
+    pub fn load_rom_with_clock(
+        rom_path: &str,
+        clock: Box<dyn Clock>,
+    ) -> Result<Cartridge, EmuError> {
         let mut file = std::fs::File::open(rom_path)?;
         let mut buffer = vec![];
         file.read_to_end(&mut buffer)?;
-        Self::load_power_up("power_up/dmg.bin", &mut buffer)?;
-        let cartridge_header = CartridgeHeader::from_bytes(&buffer[HEADER_OFFSET..=HEADER_END])?;
-        let cartridge_type = cartridge_header.cartridge_type[0];
-        let cartridge = Cartridge {
+        let header_bytes = buffer
+            .get(HEADER_OFFSET..=HEADER_END)
+            .ok_or_else(|| error::invalid_rom("Rom is too small to hold a header"))?;
+        let cartridge_header = CartridgeHeader::from_bytes(header_bytes)?;
+        let save_file = cartridge_header
+            .has_battery()
+            .then(|| SaveFile::for_rom(rom_path));
+        let mut mbc = Self::load_mbc(&cartridge_header, buffer, clock)?;
+        if let Some(save_file) = &save_file {
+            save_file.read(mbc.as_mut())?;
+        }
+        Ok(Cartridge {
+            mbc,
             header: cartridge_header,
-            mbc: Self::load_mbc(cartridge_type, buffer),
-        };
-        Ok(cartridge)
+            save_file,
+        })
     }
 
-    pub fn load_power_up(path: &str, rom: &mut Vec<u8>) -> Result<(), io::Error> {
-        let mut file = std::fs::File::open(path)?;
-        let mut buffer = vec![];
-        file.read_to_end(&mut buffer)?;
-        if rom.len() < buffer.len() {
-            return Err(error::invalid_rom_size());
-        }
-        unsafe {
-            ptr::copy_nonoverlapping(buffer.as_ptr(), rom.as_mut_ptr(), buffer.len());
+    /// The save file, to write to while the controller is lent to the MMU.
+    /// `None` for cartridges without a battery.
+    pub fn save_file(&self) -> Option<SaveFile> {
+        self.save_file.clone()
+    }
+
+    /// Writes the battery-backed state next to the ROM. Does nothing for
+    /// cartridges without a battery.
+    pub fn save(&self) -> Result<(), EmuError> {
+        match &self.save_file {
+            Some(save_file) => save_file.write(self.mbc.as_ref()),
+            None => Ok(()),
         }
-        Ok(())
     }
 }
@@ -1,3 +1,5 @@
+use crate::error::{self, EmuError};
+
 pub const HEADER_OFFSET: usize = 0x100;
 pub const HEADER_END: usize = 0x14F;
 
@@ -51,10 +53,9 @@ pub struct CartridgeHeader {
 }
 
 impl CartridgeHeader {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, std::io::Error> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EmuError> {
         if bytes.len() < std::mem::size_of::<Self>() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
+            return Err(error::invalid_rom(
                 "Insufficient bytes to parse the cartridge header",
             ));
         }
@@ -62,4 +63,56 @@ impl CartridgeHeader {
         let cartridge_header = unsafe { ptr.read() };
         Ok(cartridge_header)
     }
+
+    /// ROM size in bytes declared by the header: 32 KiB shifted left by the
+    /// size code.
+    pub fn rom_size(&self) -> usize {
+        match self.rom_size[0] {
+            code @ 0x00..=0x08 => 0x8000 << code,
+            _ => 0x8000,
+        }
+    }
+
+    /// Whether the cartridge type includes a battery keeping RAM (and the
+    /// clock, if any) powered.
+    pub fn has_battery(&self) -> bool {
+        matches!(
+            self.cartridge_type[0],
+            0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22 | 0xFF
+        )
+    }
+
+    /// External RAM size in bytes declared by the header.
+    pub fn ram_size(&self) -> usize {
+        match self.ram_size[0] {
+            0x02 => 0x2000,
+            0x03 => 0x8000,
+            0x04 => 0x20000,
+            0x05 => 0x10000,
+            _ => 0,
+        }
+    }
+
+    /// The CGB flag overlaps the last title byte on CGB-era cartridges.
+    pub fn cgb_flag(&self) -> u8 {
+        self.title[(CGB_FLAG_OFFSET - TITLE_OFFSET) as usize]
+    }
+
+    /// Sum of the 16 title bytes, used by the CGB boot ROM to pick a
+    /// compatibility palette.
+    pub fn title_checksum(&self) -> u8 {
+        self.title
+            .iter()
+            .fold(0u8, |checksum, byte| checksum.wrapping_add(*byte))
+    }
+
+    /// Whether the licensee is Nintendo, through either the old or the new
+    /// licensee code.
+    pub fn nintendo_licensee(&self) -> bool {
+        match self.old_licensee_code[0] {
+            0x01 => true,
+            0x33 => self.new_licensee_code == *b"01",
+            _ => false,
+        }
+    }
 }
@@ -1,26 +1,49 @@
-use std::ops::{Index, IndexMut};
+use crate::cartridge::{load_ram, MemoryBankController};
 
 #[derive(Debug)]
 pub struct MBC0 {
     rom: Vec<u8>,
+    ram: Vec<u8>,
 }
 
 impl MBC0 {
-    pub fn new(data: Vec<u8>) -> Self {
-        MBC0 { rom: data }
+    pub fn new(data: Vec<u8>, ram_size: usize) -> Self {
+        MBC0 {
+            rom: data,
+            ram: vec![0; ram_size],
+        }
+    }
+
+    pub fn rom_size(&self) -> usize {
+        self.rom.len()
     }
 }
 
-impl Index<usize> for MBC0 {
-    type Output = u8;
+/// No banking controller: 32 KiB of ROM and at most 8 KiB of RAM mapped
+/// directly.
+impl MemoryBankController for MBC0 {
+    fn rom_read(&self, address: usize) -> u8 {
+        self.rom.get(address).copied().unwrap_or(0xFF)
+    }
 
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.rom[index]
+    fn rom_write(&mut self, _address: usize, _value: u8) {}
+
+    /// External RAM, open bus when the cartridge has none.
+    fn ram_read(&self, address: usize) -> u8 {
+        self.ram.get(address).copied().unwrap_or(0xFF)
+    }
+
+    fn ram_write(&mut self, address: usize, value: u8) {
+        if let Some(byte) = self.ram.get_mut(address) {
+            *byte = value;
+        }
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.clone()
     }
-}
 
-impl IndexMut<usize> for MBC0 {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        &mut self.rom[index]
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
     }
 }
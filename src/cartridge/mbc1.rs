@@ -0,0 +1,248 @@
+use crate::cartridge::{
+    enables_ram, load_ram, rom_bank_count, MemoryBankController, RamWrites, ROM_BANK_SIZE,
+};
+
+const RAM_BANK_SIZE: usize = 0x2000;
+
+const RAM_ENABLE_END: usize = 0x1FFF;
+const BANK1_START: usize = 0x2000;
+const BANK1_END: usize = 0x3FFF;
+const BANK2_START: usize = 0x4000;
+const BANK2_END: usize = 0x5FFF;
+const ROM_BANK0_END: usize = 0x3FFF;
+
+const BANK1_MASK: u8 = 0b11111;
+const BANK2_MASK: u8 = 0b11;
+const MODE_MASK: u8 = 0b1;
+
+/// BANK2 sits above the 5 bits of BANK1, or above 4 of them on multicarts
+/// where BANK1 bit 4 isn't wired.
+const BANK2_SHIFT: u32 = 5;
+const MULTICART_BANK2_SHIFT: u32 = 4;
+
+/// MBC1M multicarts are 8 Mbit with a game, and so a logo, every 256 KiB.
+const MULTICART_ROM_SIZE: usize = 0x100000;
+const MULTICART_GAME_SIZE: usize = 0x40000;
+const LOGO_START: usize = 0x0104;
+const LOGO_END: usize = 0x0134;
+
+/// MBC1: up to 2 MiB of ROM and 32 KiB of RAM.
+///
+/// BANK1 (5 bits, 0 reads as 1) selects the 0x4000-0x7FFF bank and BANK2
+/// (2 bits) extends it. In mode 1, BANK2 also maps 0x0000-0x3FFF and
+/// selects the RAM bank.
+#[derive(Debug)]
+pub struct MBC1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    writes: RamWrites,
+    bank1: u8,
+    bank2: u8,
+    mode: u8,
+    rom_bank_count: usize,
+    bank2_shift: u32,
+}
+
+impl MBC1 {
+    pub fn new(data: Vec<u8>, rom_size: usize, ram_size: usize) -> Self {
+        let bank2_shift = match is_multicart(&data) {
+            true => MULTICART_BANK2_SHIFT,
+            false => BANK2_SHIFT,
+        };
+        MBC1 {
+            rom: data,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            writes: RamWrites::default(),
+            bank1: 1,
+            bank2: 0,
+            mode: 0,
+            rom_bank_count: rom_bank_count(rom_size),
+            bank2_shift,
+        }
+    }
+
+    fn bank1_mask(&self) -> u8 {
+        (1 << self.bank2_shift) - 1
+    }
+
+    fn rom_bank0(&self) -> usize {
+        match self.mode {
+            0 => 0,
+            _ => ((self.bank2 as usize) << self.bank2_shift) % self.rom_bank_count,
+        }
+    }
+
+    fn ram_offset(&self, address: usize) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+        let bank = match self.mode {
+            0 => 0,
+            _ => self.bank2 as usize,
+        };
+        Some((bank * RAM_BANK_SIZE + address) % self.ram.len())
+    }
+}
+
+impl MemoryBankController for MBC1 {
+    fn rom_read(&self, address: usize) -> u8 {
+        let (bank, offset) = match address {
+            0..=ROM_BANK0_END => (self.rom_bank0(), address),
+            _ => (self.rom_bank(), address - ROM_BANK_SIZE),
+        };
+        self.rom
+            .get(bank * ROM_BANK_SIZE + offset)
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    fn rom_write(&mut self, address: usize, value: u8) {
+        match address {
+            0..=RAM_ENABLE_END => self.ram_enabled = enables_ram(value),
+            // The 0 -> 1 translation looks at all 5 bits, so 0x20, 0x40 and
+            // 0x60 can't be mapped at 0x4000 in mode 0.
+            BANK1_START..=BANK1_END => self.bank1 = (value & BANK1_MASK).max(1),
+            BANK2_START..=BANK2_END => self.bank2 = value & BANK2_MASK,
+            _ => self.mode = value & MODE_MASK,
+        }
+    }
+
+    /// Disabled or missing RAM reads as open bus.
+    fn ram_read(&self, address: usize) -> u8 {
+        self.ram_offset(address)
+            .map_or(0xFF, |offset| self.ram[offset])
+    }
+
+    fn ram_write(&mut self, address: usize, value: u8) {
+        if let Some(offset) = self.ram_offset(address) {
+            self.ram[offset] = value;
+            self.writes.record();
+        }
+    }
+
+    fn rom_bank(&self) -> usize {
+        let bank =
+            (self.bank2 as usize) << self.bank2_shift | (self.bank1 & self.bank1_mask()) as usize;
+        bank % self.rom_bank_count
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+
+    fn take_flush_request(&mut self) -> bool {
+        self.writes.take_flush_request(self.ram_enabled)
+    }
+}
+
+/// Guesses MBC1M wiring from the logo of the second game's header, since
+/// the cartridge type doesn't tell multicarts apart.
+fn is_multicart(rom: &[u8]) -> bool {
+    if rom.len() != MULTICART_ROM_SIZE {
+        return false;
+    }
+    let logo = &rom[LOGO_START..LOGO_END];
+    logo == &rom[MULTICART_GAME_SIZE + LOGO_START..MULTICART_GAME_SIZE + LOGO_END]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::RAM_ENABLE_VALUE;
+
+    const RAM_SIZE: usize = 0x8000;
+
+    /// Every byte of a bank holds the bank number.
+    fn rom(banks: usize) -> Vec<u8> {
+        (0..banks)
+            .flat_map(|bank| vec![bank as u8; ROM_BANK_SIZE])
+            .collect()
+    }
+
+    fn mbc1(banks: usize) -> MBC1 {
+        MBC1::new(rom(banks), banks * ROM_BANK_SIZE, RAM_SIZE)
+    }
+
+    /// Four games, each starting with the same logo.
+    fn multicart() -> MBC1 {
+        let mut rom = rom(MULTICART_ROM_SIZE / ROM_BANK_SIZE);
+        for game in rom.chunks_mut(MULTICART_GAME_SIZE) {
+            for (index, byte) in game[LOGO_START..LOGO_END].iter_mut().enumerate() {
+                *byte = index as u8;
+            }
+        }
+        MBC1::new(rom, MULTICART_ROM_SIZE, 0)
+    }
+
+    #[test]
+    fn bank1_zero_maps_the_next_bank() {
+        let mut mbc = mbc1(128);
+        mbc.rom_write(BANK1_START, 0x00);
+        assert_eq!(mbc.rom_read(0x4000), 0x01);
+        for bank2 in 1..=3 {
+            mbc.rom_write(BANK2_START, bank2);
+            assert_eq!(mbc.rom_read(0x4000), (bank2 << BANK2_SHIFT) + 1);
+        }
+    }
+
+    #[test]
+    fn mode_1_maps_bank2_at_0000() {
+        let mut mbc = mbc1(128);
+        mbc.rom_write(BANK2_START, 2);
+        assert_eq!(mbc.rom_read(0x0000), 0x00);
+        mbc.rom_write(0x6000, 1);
+        assert_eq!(mbc.rom_read(0x0000), 0x40);
+        assert_eq!(mbc.rom_read(0x4000), 0x41);
+    }
+
+    #[test]
+    fn mode_1_selects_the_ram_bank() {
+        let mut mbc = mbc1(4);
+        mbc.rom_write(0x0000, RAM_ENABLE_VALUE);
+        mbc.rom_write(0x6000, 1);
+        for bank in 0..4 {
+            mbc.rom_write(BANK2_START, bank);
+            mbc.ram_write(0, 0x10 + bank);
+        }
+        mbc.rom_write(BANK2_START, 2);
+        assert_eq!(mbc.ram_read(0), 0x12);
+        mbc.rom_write(0x6000, 0);
+        assert_eq!(mbc.ram_read(0), 0x10);
+    }
+
+    #[test]
+    fn banks_wrap_to_the_header_rom_size() {
+        let mut mbc = MBC1::new(rom(32), 16 * ROM_BANK_SIZE, 0);
+        mbc.rom_write(BANK1_START, 0x11);
+        assert_eq!(mbc.rom_read(0x4000), 0x01);
+        mbc.rom_write(BANK2_START, 1);
+        mbc.rom_write(0x6000, 1);
+        assert_eq!(mbc.rom_read(0x0000), 0x00);
+    }
+
+    #[test]
+    fn multicart_bank1_has_4_bits() {
+        let mut mbc = multicart();
+        mbc.rom_write(BANK2_START, 1);
+        mbc.rom_write(BANK1_START, 0x12);
+        assert_eq!(mbc.rom_read(0x4000), 0x12);
+        mbc.rom_write(BANK1_START, 0x02);
+        assert_eq!(mbc.rom_read(0x4000), 0x12);
+        mbc.rom_write(0x6000, 1);
+        assert_eq!(mbc.rom_read(0x0000), 0x10);
+    }
+
+    #[test]
+    fn single_games_are_not_multicarts() {
+        let mut mbc = mbc1(MULTICART_ROM_SIZE / ROM_BANK_SIZE);
+        mbc.rom_write(BANK2_START, 1);
+        mbc.rom_write(BANK1_START, 0x02);
+        assert_eq!(mbc.rom_read(0x4000), 0x22);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::cartridge::{
+    enables_ram, rom_bank_count, MemoryBankController, RamWrites, ROM_BANK_SIZE,
+};
+
+const ROM_BANK0_END: usize = 0x3FFF;
+const REGISTERS_END: usize = 0x3FFF;
+
+/// Address bit 8 selects between RAM enable and the ROM bank register.
+const REGISTER_SELECT: usize = 0x0100;
+const ROM_BANK_MASK: u8 = 0x0F;
+
+/// 512 half-bytes of RAM inside the controller, mirrored across
+/// 0xA000-0xBFFF.
+const RAM_SIZE: usize = 0x200;
+const RAM_ADDRESS_MASK: usize = RAM_SIZE - 1;
+const NIBBLE_MASK: u8 = 0x0F;
+const UNUSED_BITS: u8 = 0xF0;
+
+/// MBC2: up to 256 KiB of ROM and a built-in 512x4-bit RAM.
+#[derive(Debug)]
+pub struct MBC2 {
+    rom: Vec<u8>,
+    ram: [u8; RAM_SIZE],
+    ram_enabled: bool,
+    writes: RamWrites,
+    rom_bank: u8,
+    rom_bank_count: usize,
+}
+
+impl MBC2 {
+    pub fn new(data: Vec<u8>, rom_size: usize) -> Self {
+        MBC2 {
+            rom: data,
+            ram: [0; RAM_SIZE],
+            ram_enabled: false,
+            writes: RamWrites::default(),
+            rom_bank: 1,
+            rom_bank_count: rom_bank_count(rom_size),
+        }
+    }
+}
+
+impl MemoryBankController for MBC2 {
+    fn rom_read(&self, address: usize) -> u8 {
+        let offset = match address {
+            0..=ROM_BANK0_END => address,
+            _ => self.rom_bank() * ROM_BANK_SIZE + address - ROM_BANK_SIZE,
+        };
+        self.rom.get(offset).copied().unwrap_or(0xFF)
+    }
+
+    /// Only 0x0000-0x3FFF holds registers.
+    fn rom_write(&mut self, address: usize, value: u8) {
+        match address {
+            0..=REGISTERS_END if address & REGISTER_SELECT == 0 => {
+                self.ram_enabled = enables_ram(value)
+            }
+            0..=REGISTERS_END => self.rom_bank = (value & ROM_BANK_MASK).max(1),
+            _ => {}
+        }
+    }
+
+    /// The upper nibbles aren't wired and read as 1, like disabled RAM.
+    fn ram_read(&self, address: usize) -> u8 {
+        match self.ram_enabled {
+            true => UNUSED_BITS | self.ram[address & RAM_ADDRESS_MASK],
+            false => 0xFF,
+        }
+    }
+
+    fn ram_write(&mut self, address: usize, value: u8) {
+        if self.ram_enabled {
+            self.ram[address & RAM_ADDRESS_MASK] = value & NIBBLE_MASK;
+            self.writes.record();
+        }
+    }
+
+    fn rom_bank(&self) -> usize {
+        self.rom_bank as usize % self.rom_bank_count
+    }
+
+    /// One byte per half-byte cell.
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        for (cell, byte) in self.ram.iter_mut().zip(data) {
+            *cell = byte & NIBBLE_MASK;
+        }
+    }
+
+    fn take_flush_request(&mut self) -> bool {
+        self.writes.take_flush_request(self.ram_enabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::RAM_ENABLE_VALUE;
+
+    const ROM_BANKS: usize = 16;
+
+    /// Every byte of a bank holds the bank number.
+    fn mbc2() -> MBC2 {
+        let rom = (0..ROM_BANKS)
+            .flat_map(|bank| vec![bank as u8; ROM_BANK_SIZE])
+            .collect();
+        MBC2::new(rom, ROM_BANKS * ROM_BANK_SIZE)
+    }
+
+    #[test]
+    fn address_bit_8_selects_the_register() {
+        let mut mbc = mbc2();
+        mbc.rom_write(0x0100, 0x05);
+        assert_eq!(mbc.rom_read(0x4000), 0x05);
+        mbc.rom_write(0x0000, RAM_ENABLE_VALUE);
+        assert_eq!(mbc.rom_read(0x4000), 0x05);
+        mbc.ram_write(0, 0x03);
+        assert_eq!(mbc.ram_read(0), 0xF3);
+
+        // RAM enable values written with bit 8 set select a bank instead.
+        mbc.rom_write(0x3F00, RAM_ENABLE_VALUE);
+        assert_eq!(mbc.rom_read(0x4000), 0x0A);
+        assert_eq!(mbc.ram_read(0), 0xF3);
+        mbc.rom_write(0x3EFF, 0x00);
+        assert_eq!(mbc.ram_read(0), 0xFF);
+    }
+
+    #[test]
+    fn bank_0_maps_bank_1() {
+        let mut mbc = mbc2();
+        mbc.rom_write(0x2100, 0x10);
+        assert_eq!(mbc.rom_read(0x4000), 0x01);
+    }
+
+    #[test]
+    fn ram_mirrors_every_512_bytes() {
+        let mut mbc = mbc2();
+        mbc.rom_write(0x0000, RAM_ENABLE_VALUE);
+        mbc.ram_write(0x0005, 0x0C);
+        for mirror in (0..0x2000).step_by(RAM_SIZE) {
+            assert_eq!(mbc.ram_read(mirror + 0x0005), 0xFC);
+        }
+        mbc.ram_write(0x1FFF, 0x07);
+        assert_eq!(mbc.ram_read(0x01FF), 0xF7);
+    }
+
+    #[test]
+    fn upper_nibbles_read_as_1() {
+        let mut mbc = mbc2();
+        mbc.rom_write(0x0000, RAM_ENABLE_VALUE);
+        mbc.ram_write(0, 0x00);
+        assert_eq!(mbc.ram_read(0), 0xF0);
+        mbc.ram_write(1, 0xA5);
+        assert_eq!(mbc.ram_read(1), 0xF5);
+        assert_eq!(mbc.save_data()[1], 0x05);
+    }
+}
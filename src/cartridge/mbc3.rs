@@ -0,0 +1,255 @@
+use crate::cartridge::rtc::{Rtc, DAY_HIGH_REGISTER, SECONDS_REGISTER};
+use crate::cartridge::{
+    enables_ram, load_ram, rom_bank_count, MemoryBankController, RamWrites, ROM_BANK_SIZE,
+};
+
+const RAM_BANK_SIZE: usize = 0x2000;
+const ROM_BANK0_END: usize = 0x3FFF;
+
+const RAM_ENABLE_END: usize = 0x1FFF;
+const ROM_BANK_START: usize = 0x2000;
+const ROM_BANK_END: usize = 0x3FFF;
+const RAM_BANK_START: usize = 0x4000;
+const RAM_BANK_END: usize = 0x5FFF;
+
+const ROM_BANK_MASK: u8 = 0x7F;
+const RAM_BANK_MASK: u8 = 0x03;
+/// MBC30 wires all 8 ROM bank bits and 3 RAM bank bits.
+const MBC30_ROM_BANK_MASK: u8 = 0xFF;
+const MBC30_RAM_BANK_MASK: u8 = 0x07;
+const MBC3_MAX_ROM_SIZE: usize = 0x200000;
+const MBC3_MAX_RAM_SIZE: usize = 0x8000;
+
+/// The RTC latches on a 0x00 then 0x01 write to 0x6000-0x7FFF.
+const LATCH_PREPARE: u8 = 0x00;
+const LATCH: u8 = 0x01;
+
+/// MBC3: up to 2 MiB of ROM, 32 KiB of RAM and an optional real-time clock
+/// mapped at 0xA000-0xBFFF instead of RAM by selecting 0x08-0x0C. MBC30,
+/// found in Japanese Pocket Monsters Crystal, doubles both.
+#[derive(Debug)]
+pub struct MBC3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rtc: Option<Rtc>,
+    ram_enabled: bool,
+    writes: RamWrites,
+    rom_bank: u8,
+    /// RAM bank, or RTC register when 0x08-0x0C.
+    ram_bank: u8,
+    latch_prepared: bool,
+    rom_bank_mask: u8,
+    ram_bank_mask: u8,
+    rom_bank_count: usize,
+}
+
+impl MBC3 {
+    /// Sizes beyond what MBC3 can address mean MBC30.
+    pub fn new(data: Vec<u8>, rom_size: usize, ram_size: usize, rtc: Option<Rtc>) -> Self {
+        let mbc30 = rom_size > MBC3_MAX_ROM_SIZE || ram_size > MBC3_MAX_RAM_SIZE;
+        let (rom_bank_mask, ram_bank_mask) = match mbc30 {
+            true => (MBC30_ROM_BANK_MASK, MBC30_RAM_BANK_MASK),
+            false => (ROM_BANK_MASK, RAM_BANK_MASK),
+        };
+        MBC3 {
+            rom: data,
+            ram: vec![0; ram_size],
+            rtc,
+            ram_enabled: false,
+            writes: RamWrites::default(),
+            rom_bank: 1,
+            ram_bank: 0,
+            latch_prepared: false,
+            rom_bank_mask,
+            ram_bank_mask,
+            rom_bank_count: rom_bank_count(rom_size),
+        }
+    }
+
+    fn ram_offset(&self, address: usize) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+        let bank = (self.ram_bank & self.ram_bank_mask) as usize;
+        Some((bank * RAM_BANK_SIZE + address) % self.ram.len())
+    }
+
+    fn rtc_selected(&self) -> bool {
+        (SECONDS_REGISTER..=DAY_HIGH_REGISTER).contains(&self.ram_bank)
+    }
+}
+
+impl MemoryBankController for MBC3 {
+    fn rom_read(&self, address: usize) -> u8 {
+        let offset = match address {
+            0..=ROM_BANK0_END => address,
+            _ => self.rom_bank() * ROM_BANK_SIZE + address - ROM_BANK_SIZE,
+        };
+        self.rom.get(offset).copied().unwrap_or(0xFF)
+    }
+
+    fn rom_write(&mut self, address: usize, value: u8) {
+        match address {
+            0..=RAM_ENABLE_END => self.ram_enabled = enables_ram(value),
+            ROM_BANK_START..=ROM_BANK_END => self.rom_bank = (value & self.rom_bank_mask).max(1),
+            RAM_BANK_START..=RAM_BANK_END => self.ram_bank = value,
+            _ => {
+                if self.latch_prepared && value == LATCH {
+                    if let Some(rtc) = self.rtc.as_mut() {
+                        rtc.latch();
+                    }
+                }
+                self.latch_prepared = value == LATCH_PREPARE;
+            }
+        }
+    }
+
+    /// Disabled RAM, missing RAM and unmapped bank numbers read as open bus.
+    fn ram_read(&self, address: usize) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        match (self.rtc_selected(), self.rtc.as_ref()) {
+            (true, Some(rtc)) => rtc.read(self.ram_bank),
+            (true, None) => 0xFF,
+            (false, _) if self.ram_bank > self.ram_bank_mask => 0xFF,
+            (false, _) => self
+                .ram_offset(address)
+                .map_or(0xFF, |offset| self.ram[offset]),
+        }
+    }
+
+    fn ram_write(&mut self, address: usize, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        if self.rtc_selected() {
+            if let Some(rtc) = self.rtc.as_mut() {
+                rtc.write(self.ram_bank, value);
+                self.writes.record();
+            }
+        } else if self.ram_bank <= self.ram_bank_mask {
+            if let Some(offset) = self.ram_offset(address) {
+                self.ram[offset] = value;
+                self.writes.record();
+            }
+        }
+    }
+
+    fn rom_bank(&self) -> usize {
+        self.rom_bank as usize % self.rom_bank_count
+    }
+
+    fn tick(&mut self, cycles: u8, double_speed: bool) {
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.tick(cycles, double_speed);
+        }
+    }
+
+    /// RAM followed by the RTC footer, if there is a clock.
+    fn save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        if let Some(rtc) = self.rtc.as_ref() {
+            rtc.save(&mut data);
+        }
+        data
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let length = load_ram(&mut self.ram, data);
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.load(&data[length..]);
+        }
+    }
+
+    fn take_flush_request(&mut self) -> bool {
+        self.writes.take_flush_request(self.ram_enabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::rtc::MINUTES_REGISTER;
+    use crate::cartridge::RAM_ENABLE_VALUE;
+    use crate::clock::FixedClock;
+
+    const RAM_SIZE: usize = 0x8000;
+    const M_CYCLES_PER_MINUTE: usize = 60 * 1024 * 1024;
+
+    /// Every byte of a bank holds the bank number.
+    fn rom(banks: usize) -> Vec<u8> {
+        (0..banks)
+            .flat_map(|bank| vec![bank as u8; ROM_BANK_SIZE])
+            .collect()
+    }
+
+    fn mbc3(banks: usize, ram_size: usize) -> MBC3 {
+        let rtc = Some(Rtc::new(Box::new(FixedClock::new(0))));
+        let mut mbc = MBC3::new(rom(banks), banks * ROM_BANK_SIZE, ram_size, rtc);
+        mbc.rom_write(0x0000, RAM_ENABLE_VALUE);
+        mbc
+    }
+
+    #[test]
+    fn rom_bank_0_maps_bank_1() {
+        let mut mbc = mbc3(128, RAM_SIZE);
+        mbc.rom_write(ROM_BANK_START, 0x00);
+        assert_eq!(mbc.rom_read(0x4000), 0x01);
+        mbc.rom_write(ROM_BANK_START, 0x7F);
+        assert_eq!(mbc.rom_read(0x4000), 0x7F);
+        // Bit 7 isn't wired on MBC3.
+        mbc.rom_write(ROM_BANK_START, 0x85);
+        assert_eq!(mbc.rom_read(0x4000), 0x05);
+    }
+
+    #[test]
+    fn mbc30_wires_8_rom_and_3_ram_bank_bits() {
+        let mut mbc = mbc3(256, 2 * RAM_SIZE);
+        mbc.rom_write(ROM_BANK_START, 0x85);
+        assert_eq!(mbc.rom_read(0x4000), 0x85);
+        for bank in 0..8 {
+            mbc.rom_write(RAM_BANK_START, bank);
+            mbc.ram_write(0, 0x10 + bank);
+        }
+        mbc.rom_write(RAM_BANK_START, 0x07);
+        assert_eq!(mbc.ram_read(0), 0x17);
+        mbc.rom_write(RAM_BANK_START, 0x04);
+        assert_eq!(mbc.ram_read(0), 0x14);
+    }
+
+    #[test]
+    fn mbc3_has_2_ram_bank_bits() {
+        let mut mbc = mbc3(128, RAM_SIZE);
+        mbc.rom_write(RAM_BANK_START, 0x04);
+        mbc.ram_write(0, 0x55);
+        assert_eq!(mbc.ram_read(0), 0xFF);
+        assert!(!mbc.take_flush_request());
+    }
+
+    #[test]
+    fn latches_on_a_0_then_1_write() {
+        let mut mbc = mbc3(4, RAM_SIZE);
+        mbc.rom_write(RAM_BANK_START, MINUTES_REGISTER);
+        mbc.ram_write(0, 12);
+        let latch = |mbc: &mut MBC3, values: &[u8]| {
+            for value in values {
+                mbc.rom_write(0x6000, *value);
+            }
+        };
+        mbc.rom_write(RAM_BANK_START, MINUTES_REGISTER);
+        assert_eq!(mbc.ram_read(0), 12);
+
+        // Counting time without a latch doesn't show through.
+        for _ in 0..=M_CYCLES_PER_MINUTE / u8::MAX as usize {
+            mbc.tick(u8::MAX, false);
+        }
+        assert_eq!(mbc.ram_read(0), 12);
+        latch(&mut mbc, &[LATCH]);
+        assert_eq!(mbc.ram_read(0), 12);
+        latch(&mut mbc, &[LATCH_PREPARE, 0x02, LATCH]);
+        assert_eq!(mbc.ram_read(0), 12);
+        latch(&mut mbc, &[LATCH_PREPARE, LATCH]);
+        assert_eq!(mbc.ram_read(0), 13);
+    }
+}
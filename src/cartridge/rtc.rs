@@ -0,0 +1,217 @@
+use crate::clock::Clock;
+
+pub const SECONDS_REGISTER: u8 = 0x08;
+pub const MINUTES_REGISTER: u8 = 0x09;
+pub const HOURS_REGISTER: u8 = 0x0A;
+pub const DAY_LOW_REGISTER: u8 = 0x0B;
+pub const DAY_HIGH_REGISTER: u8 = 0x0C;
+
+const SECONDS_MASK: u8 = 0b00111111;
+const MINUTES_MASK: u8 = 0b00111111;
+const HOURS_MASK: u8 = 0b00011111;
+const DAY_HIGH_BIT: u8 = 0b00000001;
+const HALT: u8 = 0b01000000;
+const DAY_CARRY: u8 = 0b10000000;
+const DAY_HIGH_MASK: u8 = DAY_HIGH_BIT | HALT | DAY_CARRY;
+
+const SECONDS_PER_MINUTE: u64 = 60;
+const MINUTES_PER_HOUR: u64 = 60;
+const HOURS_PER_DAY: u64 = 24;
+const DAY_COUNTER_RANGE: u64 = 512;
+
+/// The oscillator runs at 32768 Hz whatever the CPU speed; count time in
+/// 4 MiHz clocks, 4 per M-cycle in normal speed and 2 in double speed.
+const CLOCKS_PER_SECOND: u32 = 4 * 1024 * 1024;
+const CLOCKS_PER_M_CYCLE: u32 = 4;
+
+/// Size of the save file footer: the live then the latched registers as
+/// 32-bit words, and the time of the save in seconds since the Unix epoch.
+const SAVE_SIZE: usize = 48;
+/// Older saves store the timestamp on 32 bits.
+const SHORT_SAVE_SIZE: usize = 44;
+const TIMESTAMP_OFFSET: usize = 40;
+const REGISTER_COUNT: usize = 5;
+
+/// Real-time clock of MBC3 cartridges. The program reads a copy of the
+/// counters latched by writing 0x00 then 0x01 to 0x6000-0x7FFF.
+///
+/// While running, time advances with the emulated cycles; `clock` only
+/// dates saves and measures the time elapsed between sessions.
+#[derive(Debug)]
+pub struct Rtc {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,
+    halted: bool,
+    day_carry: bool,
+    latched: [u8; REGISTER_COUNT],
+    clocks: u32,
+    clock: Box<dyn Clock>,
+}
+
+impl Rtc {
+    pub fn new(clock: Box<dyn Clock>) -> Self {
+        Rtc {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halted: false,
+            day_carry: false,
+            latched: [0; REGISTER_COUNT],
+            clocks: 0,
+            clock,
+        }
+    }
+
+    /// Advances the clock by the M-cycles the CPU just consumed.
+    pub fn tick(&mut self, cycles: u8, double_speed: bool) {
+        if self.halted {
+            return;
+        }
+        let clocks_per_m_cycle = match double_speed {
+            true => CLOCKS_PER_M_CYCLE / 2,
+            false => CLOCKS_PER_M_CYCLE,
+        };
+        self.clocks += cycles as u32 * clocks_per_m_cycle;
+        while self.clocks >= CLOCKS_PER_SECOND {
+            self.clocks -= CLOCKS_PER_SECOND;
+            self.increment_second();
+        }
+    }
+
+    pub fn latch(&mut self) {
+        self.latched = self.registers();
+    }
+
+    pub fn read(&self, register: u8) -> u8 {
+        match register {
+            SECONDS_REGISTER..=DAY_HIGH_REGISTER => {
+                self.latched[(register - SECONDS_REGISTER) as usize]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    /// Writes go to the counters and show through the latched copy too.
+    pub fn write(&mut self, register: u8, value: u8) {
+        match register {
+            SECONDS_REGISTER => {
+                self.seconds = value & SECONDS_MASK;
+                self.clocks = 0;
+            }
+            MINUTES_REGISTER => self.minutes = value & MINUTES_MASK,
+            HOURS_REGISTER => self.hours = value & HOURS_MASK,
+            DAY_LOW_REGISTER => self.days = (self.days & 0x100) | value as u16,
+            DAY_HIGH_REGISTER => {
+                self.days = (self.days & 0xFF) | ((value & DAY_HIGH_BIT) as u16) << 8;
+                self.halted = value & HALT != 0;
+                self.day_carry = value & DAY_CARRY != 0;
+            }
+            _ => return,
+        }
+        let index = (register - SECONDS_REGISTER) as usize;
+        self.latched[index] = self.registers()[index];
+    }
+
+    /// Advances the clock by `elapsed` seconds at once, e.g. the time spent
+    /// with the emulator closed.
+    pub fn catch_up(&mut self, mut elapsed: u64) {
+        if self.halted {
+            return;
+        }
+        // Counters written out of range only wrap at their bit width, which
+        // plain arithmetic can't follow: step until they are back in range.
+        while elapsed > 0 && !self.in_range() {
+            self.increment_second();
+            elapsed -= 1;
+        }
+        let total = self.seconds as u64
+            + SECONDS_PER_MINUTE
+                * (self.minutes as u64
+                    + MINUTES_PER_HOUR * (self.hours as u64 + HOURS_PER_DAY * self.days as u64))
+            + elapsed;
+        let (minutes, seconds) = (total / SECONDS_PER_MINUTE, total % SECONDS_PER_MINUTE);
+        let (hours, minutes) = (minutes / MINUTES_PER_HOUR, minutes % MINUTES_PER_HOUR);
+        let (days, hours) = (hours / HOURS_PER_DAY, hours % HOURS_PER_DAY);
+        self.seconds = seconds as u8;
+        self.minutes = minutes as u8;
+        self.hours = hours as u8;
+        self.days = (days % DAY_COUNTER_RANGE) as u16;
+        self.day_carry |= days >= DAY_COUNTER_RANGE;
+    }
+
+    /// Appends the save footer, stamped with the current time.
+    pub fn save(&self, data: &mut Vec<u8>) {
+        for register in self.registers().into_iter().chain(self.latched) {
+            data.extend_from_slice(&(register as u32).to_le_bytes());
+        }
+        data.extend_from_slice(&self.clock.now().to_le_bytes());
+    }
+
+    /// Restores the clock from a save footer and catches up on the time
+    /// elapsed since it was written.
+    pub fn load(&mut self, footer: &[u8]) {
+        if footer.len() < SHORT_SAVE_SIZE {
+            return;
+        }
+        let words: Vec<u8> = footer[..TIMESTAMP_OFFSET]
+            .chunks_exact(4)
+            .map(|word| word[0])
+            .collect();
+        for (register, value) in (SECONDS_REGISTER..).zip(&words[..REGISTER_COUNT]) {
+            self.write(register, *value);
+        }
+        self.latched.copy_from_slice(&words[REGISTER_COUNT..]);
+        let stored = &footer[TIMESTAMP_OFFSET..footer.len().min(SAVE_SIZE)];
+        let mut timestamp = [0; 8];
+        timestamp[..stored.len()].copy_from_slice(stored);
+        let timestamp = u64::from_le_bytes(timestamp);
+        self.catch_up(self.clock.now().saturating_sub(timestamp));
+    }
+
+    fn registers(&self) -> [u8; REGISTER_COUNT] {
+        let day_high = (self.days >> 8) as u8
+            | if self.halted { HALT } else { 0 }
+            | if self.day_carry { DAY_CARRY } else { 0 };
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days as u8,
+            day_high & DAY_HIGH_MASK,
+        ]
+    }
+
+    fn in_range(&self) -> bool {
+        (self.seconds as u64) < SECONDS_PER_MINUTE
+            && (self.minutes as u64) < MINUTES_PER_HOUR
+            && (self.hours as u64) < HOURS_PER_DAY
+    }
+
+    /// Each counter carries into the next when it reaches its limit, and
+    /// wraps to 0 without carrying when it overflows its bits instead.
+    fn increment_second(&mut self) {
+        self.seconds = (self.seconds + 1) & SECONDS_MASK;
+        if self.seconds as u64 != SECONDS_PER_MINUTE {
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & MINUTES_MASK;
+        if self.minutes as u64 != MINUTES_PER_HOUR {
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & HOURS_MASK;
+        if self.hours as u64 != HOURS_PER_DAY {
+            return;
+        }
+        self.hours = 0;
+        self.days += 1;
+        if self.days as u64 == DAY_COUNTER_RANGE {
+            self.days = 0;
+            self.day_carry = true;
+        }
+    }
+}
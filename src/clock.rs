@@ -0,0 +1,70 @@
+use std::cell::Cell;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the current time, in seconds since the Unix epoch. Only
+/// things outliving an emulation session need it, like the cartridge RTC
+/// catching up on the time spent with the emulator closed.
+pub trait Clock: fmt::Debug {
+    fn now(&self) -> u64;
+}
+
+/// The host wall clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs())
+    }
+}
+
+/// Always the same time, so runs don't depend on when they happen.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock {
+    now: u64,
+}
+
+impl FixedClock {
+    pub fn new(now: u64) -> Self {
+        FixedClock { now }
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.now
+    }
+}
+
+/// A time moved forward by hand. Clones share it, so one can be handed to
+/// the emulator and the other kept to step it.
+#[derive(Debug, Default, Clone)]
+pub struct SteppedClock {
+    now: Rc<Cell<u64>>,
+}
+
+impl SteppedClock {
+    pub fn new(now: u64) -> Self {
+        SteppedClock {
+            now: Rc::new(Cell::new(now)),
+        }
+    }
+
+    pub fn advance(&self, seconds: u64) {
+        self.now.set(self.now.get() + seconds);
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.set(now);
+    }
+}
+
+impl Clock for SteppedClock {
+    fn now(&self) -> u64 {
+        self.now.get()
+    }
+}
@@ -1,8 +1,16 @@
+pub mod decoder;
+pub mod disasm;
 mod instructions;
-mod registers;
+pub mod registers;
+pub mod trace;
 
-use crate::mmu::MMU;
-use std::{io, mem};
+use crate::bus::Bus;
+use crate::cpu::disasm::Disassembly;
+use crate::error::EmuError;
+use crate::interrupts::{Interrupt, INTERRUPT_SERVICE_CYCLES};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::mem;
 
 pub const BLOCK_MASK: u8 = 0b11000000;
 pub const BLOCK_SHIFT: u8 = 6;
@@ -14,9 +22,39 @@ pub const BLOCK_3: u8 = 0b11;
 pub type Word = u8;
 pub type DWord = u16;
 
+/// Number of `run` calls after EI before IME is actually set: the
+/// instruction following EI still runs with interrupts disabled.
+const EI_DELAY: u8 = 2;
+
+/// M-cycles the CPU stays stopped while a CGB speed switch settles.
+const SPEED_SWITCH_CYCLES: u16 = 2050;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Running,
+    /// Entered by HALT, left as soon as an interrupt is pending in IE & IF.
+    Halted,
+    /// Entered by STOP, left when the joypad requests an interrupt.
+    Stopped,
+    /// Remaining M-cycles of a CGB speed switch triggered by STOP.
+    SpeedSwitch(u16),
+}
+
 pub struct CPU {
     pub registers: registers::Registers,
     ime: bool,
+    ei_delay: u8,
+    state: State,
+    halt_bug: bool,
+    trace: Option<Box<dyn Write>>,
+    history: VecDeque<Disassembly>,
+    history_size: usize,
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CPU {
@@ -24,24 +62,318 @@ impl CPU {
         Self {
             registers: registers::Registers::new(),
             ime: false,
+            ei_delay: 0,
+            state: State::Running,
+            halt_bug: false,
+            trace: None,
+            history: VecDeque::new(),
+            history_size: 0,
+        }
+    }
+
+    /// Executes the next instruction and returns the number of M-cycles it
+    /// consumed, so the rest of the machine can be clocked against it.
+    pub fn run(&mut self, bus: &mut impl Bus) -> Result<u8, EmuError> {
+        let cycles = self.step(bus)?;
+        bus.set_cpu_halted(self.state == State::Halted);
+        bus.tick(cycles);
+        Ok(cycles)
+    }
+
+    fn step(&mut self, bus: &mut impl Bus) -> Result<u8, EmuError> {
+        if self.ei_delay > 0 {
+            self.ei_delay -= 1;
+            self.ime |= self.ei_delay == 0;
+        }
+        if !self.wake_up(bus)? {
+            return Ok(1);
+        }
+        if let Some(cycles) = self.service_interrupt(bus)? {
+            return Ok(cycles);
+        }
+        if let Some(out) = &mut self.trace {
+            trace::write_line(out, &self.registers, bus)?;
+        }
+        let pc = self.registers.pc;
+        let instruction =
+            decoder::decode(|| self.fetch_next_word(bus)).map_err(|error| error.with_pc(pc))?;
+        if self.history_size > 0 {
+            self.record(pc, instruction);
+        }
+        instructions::execute(instruction, self, bus).map_err(|error| error.with_pc(pc))
+    }
+
+    pub fn ime(&self) -> bool {
+        self.ime
+    }
+
+    /// Sets IME directly, cancelling any pending `EI`.
+    pub fn set_ime(&mut self, ime: bool) {
+        self.ime = ime;
+        self.ei_delay = 0;
+    }
+
+    /// Logs the state before every executed instruction to `out`, see
+    /// `trace::write_line`.
+    pub fn set_trace(&mut self, out: Box<dyn Write>) {
+        self.trace = Some(out);
+    }
+
+    /// Keeps the last `size` decoded instructions for crash reports.
+    pub fn set_history_size(&mut self, size: usize) {
+        self.history_size = size;
+        self.history = VecDeque::with_capacity(size);
+    }
+
+    /// Recently decoded instructions, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &Disassembly> {
+        self.history.iter()
+    }
+
+    fn record(&mut self, address: u16, instruction: decoder::Instruction) {
+        if self.history.len() == self.history_size {
+            self.history.pop_front();
         }
+        self.history.push_back(Disassembly {
+            address,
+            length: self.registers.pc.wrapping_sub(address) as u8,
+            instruction,
+        });
     }
 
-    pub fn run(&mut self, mmu: &mut MMU) -> Result<(), io::Error> {
-        let word = self.fetch_next_word(mmu)?;
-        instructions::execute(word, self, mmu)?;
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Updates the low-power state and returns whether the CPU is running.
+    fn wake_up(&mut self, bus: &mut impl Bus) -> Result<bool, EmuError> {
+        self.state = match self.state {
+            State::Halted if bus.pending_interrupt()?.is_none() => State::Halted,
+            State::Stopped if !bus.interrupt_requested(Interrupt::Joypad)? => State::Stopped,
+            State::SpeedSwitch(remaining) if remaining > 1 => State::SpeedSwitch(remaining - 1),
+            _ => State::Running,
+        };
+        Ok(self.state == State::Running)
+    }
+
+    fn halt(&mut self, bus: &mut impl Bus) -> Result<(), EmuError> {
+        match (self.ime, bus.pending_interrupt()?) {
+            // HALT exits immediately and the next opcode byte is read twice.
+            (false, Some(_)) => self.halt_bug = true,
+            _ => self.state = State::Halted,
+        }
         Ok(())
     }
 
-    pub fn fetch_next_word(&mut self, mmu: &mut MMU) -> Result<u8, io::Error> {
-        let word = mmu.get_word(self.registers.pc as usize)?;
-        self.registers.pc += mem::size_of::<Word>() as DWord;
+    fn stop(&mut self, bus: &mut impl Bus) {
+        if bus.switch_speed() {
+            self.state = State::SpeedSwitch(SPEED_SWITCH_CYCLES);
+        } else {
+            self.state = State::Stopped;
+        }
+    }
+
+    /// Dispatches the highest priority pending interrupt if IME is set:
+    /// IME is cleared, the IF bit acknowledged, PC pushed and execution
+    /// resumes at the interrupt vector. After a buggy HALT (EI then HALT)
+    /// the pushed address is the HALT's, which runs again on return.
+    fn service_interrupt(&mut self, bus: &mut impl Bus) -> Result<Option<u8>, EmuError> {
+        if !self.ime {
+            return Ok(None);
+        }
+        let Some(interrupt) = bus.pending_interrupt()? else {
+            return Ok(None);
+        };
+        self.ime = false;
+        bus.acknowledge_interrupt(interrupt)?;
+        let return_address = match mem::take(&mut self.halt_bug) {
+            true => self.registers.pc.wrapping_sub(1),
+            false => self.registers.pc,
+        };
+        self.registers.sp = self
+            .registers
+            .sp
+            .wrapping_sub(mem::size_of::<DWord>() as DWord);
+        bus.set_dword(self.registers.sp as usize, return_address)?;
+        self.registers.pc = interrupt.vector();
+        Ok(Some(INTERRUPT_SERVICE_CYCLES))
+    }
+
+    pub fn fetch_next_word(&mut self, bus: &mut impl Bus) -> Result<u8, EmuError> {
+        let word = bus.get_word(self.registers.pc as usize)?;
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.registers.pc = self
+                .registers
+                .pc
+                .wrapping_add(mem::size_of::<Word>() as DWord);
+        }
         Ok(word)
     }
 
-    pub fn fetch_next_dword(&mut self, mmu: &mut MMU) -> Result<u16, io::Error> {
-        let dword = mmu.get_dword(self.registers.pc as usize)?;
-        self.registers.pc += mem::size_of::<DWord>() as DWord;
+    pub fn fetch_next_dword(&mut self, bus: &mut impl Bus) -> Result<u16, EmuError> {
+        let dword = bus.get_dword(self.registers.pc as usize)?;
+        self.registers.pc = self
+            .registers
+            .pc
+            .wrapping_add(mem::size_of::<DWord>() as DWord);
         Ok(dword)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{FlatBus, IE_REGISTER, IF_REGISTER};
+    use crate::interrupts::INTERRUPT_MASK;
+
+    const START: u16 = 0x0100;
+    const STACK: u16 = 0xD000;
+    const HALT: u8 = 0x76;
+    const INC_A: u8 = 0x3C;
+    const EI: u8 = 0xFB;
+    const DI: u8 = 0xF3;
+    const NOP: u8 = 0x00;
+    const RETI: u8 = 0xD9;
+
+    fn cpu() -> CPU {
+        let mut cpu = CPU::new();
+        cpu.registers.pc = START;
+        cpu.registers.sp = STACK;
+        cpu
+    }
+
+    fn request_vblank(bus: &mut FlatBus) {
+        bus.memory[IF_REGISTER] = Interrupt::VBlank.mask();
+        bus.memory[IE_REGISTER] = Interrupt::VBlank.mask();
+    }
+
+    #[test]
+    fn halt_waits_for_a_pending_interrupt() {
+        let mut bus = FlatBus::with_program(START, &[HALT, INC_A]);
+        let mut cpu = cpu();
+        cpu.run(&mut bus).unwrap();
+        assert_eq!(cpu.state(), State::Halted);
+        assert_eq!(cpu.run(&mut bus).unwrap(), 1);
+        assert_eq!(cpu.registers.a, 0);
+
+        // With IME clear, waking up resumes after HALT without dispatching.
+        request_vblank(&mut bus);
+        cpu.run(&mut bus).unwrap();
+        assert_eq!(cpu.state(), State::Running);
+        assert_eq!(cpu.registers.a, 1);
+        assert_eq!(cpu.registers.pc, START + 2);
+    }
+
+    #[test]
+    fn halt_bug_reads_the_next_byte_twice() {
+        let mut bus = FlatBus::with_program(START, &[HALT, INC_A]);
+        request_vblank(&mut bus);
+        let mut cpu = cpu();
+        cpu.run(&mut bus).unwrap();
+        assert_eq!(cpu.state(), State::Running);
+        cpu.run(&mut bus).unwrap();
+        assert_eq!(cpu.registers.pc, START + 1);
+        cpu.run(&mut bus).unwrap();
+        assert_eq!(cpu.registers.a, 2);
+        assert_eq!(cpu.registers.pc, START + 2);
+    }
+
+    #[test]
+    fn ei_then_halt_returns_to_the_halt() {
+        let mut bus = FlatBus::with_program(START, &[EI, HALT]);
+        bus.memory[0x40..0x42].copy_from_slice(&[0x3E, 0x42]);
+        request_vblank(&mut bus);
+        let mut cpu = cpu();
+        cpu.run(&mut bus).unwrap();
+        cpu.run(&mut bus).unwrap();
+        cpu.run(&mut bus).unwrap();
+        assert_eq!(cpu.registers.pc, 0x40);
+        assert_eq!(bus.get_dword(cpu.registers.sp as usize).unwrap(), START + 1);
+
+        cpu.run(&mut bus).unwrap();
+        assert_eq!(cpu.registers.a, 0x42);
+        assert_eq!(cpu.registers.pc, 0x42);
+    }
+
+    #[test]
+    fn dispatch_takes_the_highest_priority_vector() {
+        for (bit, vector) in [0x40, 0x48, 0x50, 0x58, 0x60].into_iter().enumerate() {
+            let mut bus = FlatBus::with_program(START, &[NOP]);
+            // This interrupt and every lower priority one are pending.
+            bus.memory[IF_REGISTER] = INTERRUPT_MASK & !((1 << bit) - 1);
+            bus.memory[IE_REGISTER] = INTERRUPT_MASK;
+            let mut cpu = cpu();
+            cpu.set_ime(true);
+
+            assert_eq!(cpu.run(&mut bus).unwrap(), INTERRUPT_SERVICE_CYCLES);
+            assert_eq!(cpu.registers.pc, vector);
+            assert_eq!(cpu.registers.sp, STACK - 2);
+            assert_eq!(bus.get_dword(STACK as usize - 2).unwrap(), START);
+            assert_eq!(bus.memory[IF_REGISTER], INTERRUPT_MASK & !((2 << bit) - 1));
+            assert!(!cpu.ime());
+        }
+    }
+
+    #[test]
+    fn disabled_interrupts_are_not_dispatched() {
+        let mut bus = FlatBus::with_program(START, &[NOP]);
+        bus.memory[IF_REGISTER] = Interrupt::Timer.mask();
+        bus.memory[IE_REGISTER] = Interrupt::VBlank.mask();
+        let mut cpu = cpu();
+        cpu.set_ime(true);
+        assert_eq!(cpu.run(&mut bus).unwrap(), 1);
+        assert_eq!(cpu.registers.pc, START + 1);
+    }
+
+    #[test]
+    fn ei_takes_effect_after_the_next_instruction() {
+        let mut bus = FlatBus::with_program(START, &[EI, INC_A, INC_A]);
+        request_vblank(&mut bus);
+        let mut cpu = cpu();
+        cpu.run(&mut bus).unwrap();
+        cpu.run(&mut bus).unwrap();
+        assert_eq!(cpu.registers.a, 1);
+        assert_eq!(cpu.run(&mut bus).unwrap(), INTERRUPT_SERVICE_CYCLES);
+        assert_eq!(cpu.registers.pc, 0x40);
+        assert_eq!(cpu.registers.a, 1);
+    }
+
+    #[test]
+    fn di_right_after_ei_keeps_interrupts_disabled() {
+        let mut bus = FlatBus::with_program(START, &[EI, DI, NOP, NOP]);
+        request_vblank(&mut bus);
+        let mut cpu = cpu();
+        for _ in 0..4 {
+            cpu.run(&mut bus).unwrap();
+        }
+        assert_eq!(cpu.registers.pc, START + 4);
+        assert!(!cpu.ime());
+    }
+
+    #[test]
+    fn ei_then_halt_dispatches_without_halting() {
+        let mut bus = FlatBus::with_program(START, &[EI, HALT]);
+        request_vblank(&mut bus);
+        let mut cpu = cpu();
+        cpu.run(&mut bus).unwrap();
+        cpu.run(&mut bus).unwrap();
+        assert_eq!(cpu.run(&mut bus).unwrap(), INTERRUPT_SERVICE_CYCLES);
+        assert_eq!(cpu.state(), State::Running);
+        assert_eq!(cpu.registers.pc, 0x40);
+    }
+
+    #[test]
+    fn reti_enables_interrupts_immediately() {
+        let mut bus = FlatBus::with_program(START, &[RETI]);
+        bus.set_dword(STACK as usize, 0x0200).unwrap();
+        request_vblank(&mut bus);
+        let mut cpu = cpu();
+        cpu.run(&mut bus).unwrap();
+        assert_eq!(cpu.registers.pc, 0x0200);
+        assert!(cpu.ime());
+        assert_eq!(cpu.run(&mut bus).unwrap(), INTERRUPT_SERVICE_CYCLES);
+        assert_eq!(cpu.registers.pc, 0x40);
+    }
+}
@@ -0,0 +1,328 @@
+const PREFIX_OPCODE: u8 = 0xCB;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum R8 {
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    HLMem,
+    A,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum R16 {
+    BC,
+    DE,
+    HL,
+    SP,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum R16Stk {
+    BC,
+    DE,
+    HL,
+    AF,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum R16Mem {
+    BC,
+    DE,
+    HLI,
+    HLD,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cond {
+    NZ,
+    Z,
+    NC,
+    C,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AluOp {
+    Add,
+    Adc,
+    Sub,
+    Sbc,
+    And,
+    Xor,
+    Or,
+    Cp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShiftOp {
+    Rlc,
+    Rrc,
+    Rl,
+    Rr,
+    Sla,
+    Sra,
+    Swap,
+    Srl,
+}
+
+pub type Imm8 = u8;
+pub type Imm16 = u16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Nop,
+    LdR16Imm16(R16, Imm16),
+    LdR16MemA(R16Mem),
+    LdAR16Mem(R16Mem),
+    LdImm16MemSp(Imm16),
+    IncR16(R16),
+    DecR16(R16),
+    AddHlR16(R16),
+    IncR8(R8),
+    DecR8(R8),
+    LdR8Imm8(R8, Imm8),
+    Rlca,
+    Rrca,
+    Rla,
+    Rra,
+    Daa,
+    Cpl,
+    Scf,
+    Ccf,
+    JrImm8(i8),
+    JrCcImm8(Cond, i8),
+    Stop,
+    LdR8R8(R8, R8),
+    Halt,
+    AluR8(AluOp, R8),
+    AluImm8(AluOp, Imm8),
+    RetCc(Cond),
+    Ret,
+    Reti,
+    JpCcImm16(Cond, Imm16),
+    JpImm16(Imm16),
+    JpHl,
+    CallCcImm16(Cond, Imm16),
+    CallImm16(Imm16),
+    RstTgt3(u8),
+    PopR16Stk(R16Stk),
+    PushR16Stk(R16Stk),
+    LdhCMemA,
+    LdhImm8A(Imm8),
+    LdImm16A(Imm16),
+    LdhACMem,
+    LdhAImm8(Imm8),
+    LdAImm16(Imm16),
+    AddSpImm8(i8),
+    LdHlSpImm8(i8),
+    LdSpHl,
+    Di,
+    Ei,
+    ShiftR8(ShiftOp, R8),
+    BitB3R8(u8, R8),
+    ResB3R8(u8, R8),
+    SetB3R8(u8, R8),
+    Illegal(u8),
+}
+
+const R8_TABLE: [R8; 8] = [R8::B, R8::C, R8::D, R8::E, R8::H, R8::L, R8::HLMem, R8::A];
+const R16_TABLE: [R16; 4] = [R16::BC, R16::DE, R16::HL, R16::SP];
+const R16_STK_TABLE: [R16Stk; 4] = [R16Stk::BC, R16Stk::DE, R16Stk::HL, R16Stk::AF];
+const R16_MEM_TABLE: [R16Mem; 4] = [R16Mem::BC, R16Mem::DE, R16Mem::HLI, R16Mem::HLD];
+const COND_TABLE: [Cond; 4] = [Cond::NZ, Cond::Z, Cond::NC, Cond::C];
+const ALU_TABLE: [AluOp; 8] = [
+    AluOp::Add,
+    AluOp::Adc,
+    AluOp::Sub,
+    AluOp::Sbc,
+    AluOp::And,
+    AluOp::Xor,
+    AluOp::Or,
+    AluOp::Cp,
+];
+const SHIFT_TABLE: [ShiftOp; 8] = [
+    ShiftOp::Rlc,
+    ShiftOp::Rrc,
+    ShiftOp::Rl,
+    ShiftOp::Rr,
+    ShiftOp::Sla,
+    ShiftOp::Sra,
+    ShiftOp::Swap,
+    ShiftOp::Srl,
+];
+
+static MAIN_TABLE: [Instruction; 256] = build_table(false);
+static PREFIX_TABLE: [Instruction; 256] = build_table(true);
+static LENGTH_TABLE: [u8; 256] = build_length_table();
+
+/// Decodes one instruction, pulling its opcode, CB suffix and immediates
+/// from `fetch` in memory order.
+pub fn decode<E>(mut fetch: impl FnMut() -> Result<u8, E>) -> Result<Instruction, E> {
+    let opcode = fetch()?;
+    let instruction = MAIN_TABLE[opcode as usize];
+    match LENGTH_TABLE[opcode as usize] {
+        1 => Ok(instruction),
+        2 if opcode == PREFIX_OPCODE => Ok(PREFIX_TABLE[fetch()? as usize]),
+        2 => Ok(instruction.with_imm8(fetch()?)),
+        _ => {
+            let low = fetch()?;
+            Ok(instruction.with_imm16(u16::from_le_bytes([low, fetch()?])))
+        }
+    }
+}
+
+/// Length in bytes of the instruction starting with `opcode`.
+pub fn instruction_length(opcode: u8) -> u8 {
+    LENGTH_TABLE[opcode as usize]
+}
+
+impl Instruction {
+    /// Fills the imm8 operand of a table entry decoded without immediates.
+    fn with_imm8(self, imm8: Imm8) -> Self {
+        match self {
+            Instruction::LdR8Imm8(r8, _) => Instruction::LdR8Imm8(r8, imm8),
+            Instruction::JrImm8(_) => Instruction::JrImm8(imm8 as i8),
+            Instruction::JrCcImm8(cond, _) => Instruction::JrCcImm8(cond, imm8 as i8),
+            Instruction::AluImm8(op, _) => Instruction::AluImm8(op, imm8),
+            Instruction::LdhImm8A(_) => Instruction::LdhImm8A(imm8),
+            Instruction::LdhAImm8(_) => Instruction::LdhAImm8(imm8),
+            Instruction::AddSpImm8(_) => Instruction::AddSpImm8(imm8 as i8),
+            Instruction::LdHlSpImm8(_) => Instruction::LdHlSpImm8(imm8 as i8),
+            instruction => instruction,
+        }
+    }
+
+    /// Fills the imm16 operand of a table entry decoded without immediates.
+    fn with_imm16(self, imm16: Imm16) -> Self {
+        match self {
+            Instruction::LdR16Imm16(r16, _) => Instruction::LdR16Imm16(r16, imm16),
+            Instruction::LdImm16MemSp(_) => Instruction::LdImm16MemSp(imm16),
+            Instruction::JpCcImm16(cond, _) => Instruction::JpCcImm16(cond, imm16),
+            Instruction::JpImm16(_) => Instruction::JpImm16(imm16),
+            Instruction::CallCcImm16(cond, _) => Instruction::CallCcImm16(cond, imm16),
+            Instruction::CallImm16(_) => Instruction::CallImm16(imm16),
+            Instruction::LdImm16A(_) => Instruction::LdImm16A(imm16),
+            Instruction::LdAImm16(_) => Instruction::LdAImm16(imm16),
+            instruction => instruction,
+        }
+    }
+}
+
+const fn build_table(prefixed: bool) -> [Instruction; 256] {
+    let mut table = [Instruction::Nop; 256];
+    let mut opcode = 0;
+    while opcode < table.len() {
+        table[opcode] = match prefixed {
+            true => decode_prefix(opcode as u8),
+            false => decode_opcode(opcode as u8),
+        };
+        opcode += 1;
+    }
+    table
+}
+
+const fn build_length_table() -> [u8; 256] {
+    let mut table = [1; 256];
+    let mut opcode = 0;
+    while opcode < table.len() {
+        table[opcode] = opcode_length(opcode as u8);
+        opcode += 1;
+    }
+    table
+}
+
+const fn opcode_length(opcode: u8) -> u8 {
+    match opcode {
+        0x01 | 0x11 | 0x21 | 0x31 | 0x08 => 3,
+        0xC2 | 0xC3 | 0xC4 | 0xCA | 0xCC | 0xCD | 0xD2 | 0xD4 | 0xDA | 0xDC => 3,
+        0xEA | 0xFA => 3,
+        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E => 2,
+        0x10 | 0x18 | 0x20 | 0x28 | 0x30 | 0x38 => 2,
+        0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE => 2,
+        0xCB | 0xE0 | 0xE8 | 0xF0 | 0xF8 => 2,
+        _ => 1,
+    }
+}
+
+const fn decode_opcode(opcode: u8) -> Instruction {
+    let r8_dst = R8_TABLE[((opcode >> 3) & 0b111) as usize];
+    let r8_src = R8_TABLE[(opcode & 0b111) as usize];
+    let r16 = R16_TABLE[((opcode >> 4) & 0b11) as usize];
+    let r16_stk = R16_STK_TABLE[((opcode >> 4) & 0b11) as usize];
+    let r16_mem = R16_MEM_TABLE[((opcode >> 4) & 0b11) as usize];
+    let cond = COND_TABLE[((opcode >> 3) & 0b11) as usize];
+    let alu_op = ALU_TABLE[((opcode >> 3) & 0b111) as usize];
+
+    match opcode {
+        0x00 => Instruction::Nop,
+        0x10 => Instruction::Stop,
+        0x08 => Instruction::LdImm16MemSp(0),
+        0x07 => Instruction::Rlca,
+        0x0F => Instruction::Rrca,
+        0x17 => Instruction::Rla,
+        0x1F => Instruction::Rra,
+        0x27 => Instruction::Daa,
+        0x2F => Instruction::Cpl,
+        0x37 => Instruction::Scf,
+        0x3F => Instruction::Ccf,
+        0x18 => Instruction::JrImm8(0),
+        0x20 | 0x28 | 0x30 | 0x38 => Instruction::JrCcImm8(cond, 0),
+        _ if opcode <= 0x3F => match opcode & 0b1111 {
+            0x1 => Instruction::LdR16Imm16(r16, 0),
+            0x2 => Instruction::LdR16MemA(r16_mem),
+            0x3 => Instruction::IncR16(r16),
+            0x9 => Instruction::AddHlR16(r16),
+            0xA => Instruction::LdAR16Mem(r16_mem),
+            0xB => Instruction::DecR16(r16),
+            _ => match opcode & 0b111 {
+                0x4 => Instruction::IncR8(r8_dst),
+                0x5 => Instruction::DecR8(r8_dst),
+                _ => Instruction::LdR8Imm8(r8_dst, 0),
+            },
+        },
+        0x76 => Instruction::Halt,
+        _ if opcode <= 0x7F => Instruction::LdR8R8(r8_dst, r8_src),
+        _ if opcode <= 0xBF => Instruction::AluR8(alu_op, r8_src),
+        0xC9 => Instruction::Ret,
+        0xD9 => Instruction::Reti,
+        0xC3 => Instruction::JpImm16(0),
+        0xE9 => Instruction::JpHl,
+        0xCD => Instruction::CallImm16(0),
+        0xE2 => Instruction::LdhCMemA,
+        0xE0 => Instruction::LdhImm8A(0),
+        0xEA => Instruction::LdImm16A(0),
+        0xF2 => Instruction::LdhACMem,
+        0xF0 => Instruction::LdhAImm8(0),
+        0xFA => Instruction::LdAImm16(0),
+        0xE8 => Instruction::AddSpImm8(0),
+        0xF8 => Instruction::LdHlSpImm8(0),
+        0xF9 => Instruction::LdSpHl,
+        0xF3 => Instruction::Di,
+        0xFB => Instruction::Ei,
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => Instruction::RetCc(cond),
+        0xC2 | 0xCA | 0xD2 | 0xDA => Instruction::JpCcImm16(cond, 0),
+        0xC4 | 0xCC | 0xD4 | 0xDC => Instruction::CallCcImm16(cond, 0),
+        0xC1 | 0xD1 | 0xE1 | 0xF1 => Instruction::PopR16Stk(r16_stk),
+        0xC5 | 0xD5 | 0xE5 | 0xF5 => Instruction::PushR16Stk(r16_stk),
+        0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE => Instruction::AluImm8(alu_op, 0),
+        0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => {
+            Instruction::RstTgt3(opcode & 0b00111000)
+        }
+        _ => Instruction::Illegal(opcode),
+    }
+}
+
+const fn decode_prefix(opcode: u8) -> Instruction {
+    let r8 = R8_TABLE[(opcode & 0b111) as usize];
+    let index = (opcode >> 3) & 0b111;
+
+    match opcode >> 6 {
+        0b00 => Instruction::ShiftR8(SHIFT_TABLE[index as usize], r8),
+        0b01 => Instruction::BitB3R8(index, r8),
+        0b10 => Instruction::ResB3R8(index, r8),
+        _ => Instruction::SetB3R8(index, r8),
+    }
+}
@@ -0,0 +1,260 @@
+use std::fmt;
+
+use crate::cpu::decoder::{self, AluOp, Cond, Instruction, R16Mem, R16Stk, ShiftOp, R16, R8};
+
+const LDH_ADDRESS_START: u16 = 0xFF00;
+
+/// A decoded instruction together with the address it was read from and
+/// its length in bytes, opcode and CB prefix included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disassembly {
+    pub address: u16,
+    pub length: u8,
+    pub instruction: Instruction,
+}
+
+/// Decodes the instruction starting at `bytes[0]`, which lives at
+/// `address`. Returns `None` when `bytes` ends before the instruction does.
+pub fn disassemble(bytes: &[u8], address: u16) -> Option<Disassembly> {
+    let mut words = bytes.iter().copied();
+    let instruction = decoder::decode(|| words.next().ok_or(())).ok()?;
+    Some(Disassembly {
+        address,
+        length: decoder::instruction_length(bytes[0]),
+        instruction,
+    })
+}
+
+fn relative_target(address: u16, length: u8, offset: i8) -> u16 {
+    address
+        .wrapping_add(length as u16)
+        .wrapping_add(offset as i16 as u16)
+}
+
+fn signed(offset: i8) -> String {
+    match offset < 0 {
+        true => format!("-${:02X}", offset.unsigned_abs()),
+        false => format!("${:02X}", offset),
+    }
+}
+
+impl fmt::Display for R8 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            R8::B => "B",
+            R8::C => "C",
+            R8::D => "D",
+            R8::E => "E",
+            R8::H => "H",
+            R8::L => "L",
+            R8::HLMem => "[HL]",
+            R8::A => "A",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for R16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            R16::BC => "BC",
+            R16::DE => "DE",
+            R16::HL => "HL",
+            R16::SP => "SP",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for R16Stk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            R16Stk::BC => "BC",
+            R16Stk::DE => "DE",
+            R16Stk::HL => "HL",
+            R16Stk::AF => "AF",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for R16Mem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            R16Mem::BC => "[BC]",
+            R16Mem::DE => "[DE]",
+            R16Mem::HLI => "[HL+]",
+            R16Mem::HLD => "[HL-]",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Cond::NZ => "NZ",
+            Cond::Z => "Z",
+            Cond::NC => "NC",
+            Cond::C => "C",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for AluOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AluOp::Add => "ADD",
+            AluOp::Adc => "ADC",
+            AluOp::Sub => "SUB",
+            AluOp::Sbc => "SBC",
+            AluOp::And => "AND",
+            AluOp::Xor => "XOR",
+            AluOp::Or => "OR",
+            AluOp::Cp => "CP",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for ShiftOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ShiftOp::Rlc => "RLC",
+            ShiftOp::Rrc => "RRC",
+            ShiftOp::Rl => "RL",
+            ShiftOp::Rr => "RR",
+            ShiftOp::Sla => "SLA",
+            ShiftOp::Sra => "SRA",
+            ShiftOp::Swap => "SWAP",
+            ShiftOp::Srl => "SRL",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let jr_target = |offset| relative_target(self.address, self.length, offset);
+
+        match self.instruction {
+            Instruction::Nop => write!(f, "NOP"),
+            Instruction::LdR16Imm16(r16, imm16) => write!(f, "LD {},${:04X}", r16, imm16),
+            Instruction::LdR16MemA(r16_mem) => write!(f, "LD {},A", r16_mem),
+            Instruction::LdAR16Mem(r16_mem) => write!(f, "LD A,{}", r16_mem),
+            Instruction::LdImm16MemSp(imm16) => write!(f, "LD [${:04X}],SP", imm16),
+            Instruction::IncR16(r16) => write!(f, "INC {}", r16),
+            Instruction::DecR16(r16) => write!(f, "DEC {}", r16),
+            Instruction::AddHlR16(r16) => write!(f, "ADD HL,{}", r16),
+            Instruction::IncR8(r8) => write!(f, "INC {}", r8),
+            Instruction::DecR8(r8) => write!(f, "DEC {}", r8),
+            Instruction::LdR8Imm8(r8, imm8) => write!(f, "LD {},${:02X}", r8, imm8),
+            Instruction::Rlca => write!(f, "RLCA"),
+            Instruction::Rrca => write!(f, "RRCA"),
+            Instruction::Rla => write!(f, "RLA"),
+            Instruction::Rra => write!(f, "RRA"),
+            Instruction::Daa => write!(f, "DAA"),
+            Instruction::Cpl => write!(f, "CPL"),
+            Instruction::Scf => write!(f, "SCF"),
+            Instruction::Ccf => write!(f, "CCF"),
+            Instruction::JrImm8(offset) => write!(f, "JR ${:04X}", jr_target(offset)),
+            Instruction::JrCcImm8(cond, offset) => {
+                write!(f, "JR {},${:04X}", cond, jr_target(offset))
+            }
+            Instruction::Stop => write!(f, "STOP"),
+            Instruction::LdR8R8(dst, src) => write!(f, "LD {},{}", dst, src),
+            Instruction::Halt => write!(f, "HALT"),
+            Instruction::AluR8(op, r8) => write!(f, "{} A,{}", op, r8),
+            Instruction::AluImm8(op, imm8) => write!(f, "{} A,${:02X}", op, imm8),
+            Instruction::RetCc(cond) => write!(f, "RET {}", cond),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::Reti => write!(f, "RETI"),
+            Instruction::JpCcImm16(cond, imm16) => write!(f, "JP {},${:04X}", cond, imm16),
+            Instruction::JpImm16(imm16) => write!(f, "JP ${:04X}", imm16),
+            Instruction::JpHl => write!(f, "JP HL"),
+            Instruction::CallCcImm16(cond, imm16) => write!(f, "CALL {},${:04X}", cond, imm16),
+            Instruction::CallImm16(imm16) => write!(f, "CALL ${:04X}", imm16),
+            Instruction::RstTgt3(target) => write!(f, "RST ${:02X}", target),
+            Instruction::PopR16Stk(r16_stk) => write!(f, "POP {}", r16_stk),
+            Instruction::PushR16Stk(r16_stk) => write!(f, "PUSH {}", r16_stk),
+            Instruction::LdhCMemA => write!(f, "LDH [C],A"),
+            Instruction::LdhImm8A(imm8) => {
+                write!(f, "LDH [${:04X}],A", LDH_ADDRESS_START + imm8 as u16)
+            }
+            Instruction::LdImm16A(imm16) => write!(f, "LD [${:04X}],A", imm16),
+            Instruction::LdhACMem => write!(f, "LDH A,[C]"),
+            Instruction::LdhAImm8(imm8) => {
+                write!(f, "LDH A,[${:04X}]", LDH_ADDRESS_START + imm8 as u16)
+            }
+            Instruction::LdAImm16(imm16) => write!(f, "LD A,[${:04X}]", imm16),
+            Instruction::AddSpImm8(offset) => write!(f, "ADD SP,{}", signed(offset)),
+            Instruction::LdHlSpImm8(offset) if offset < 0 => {
+                write!(f, "LD HL,SP-${:02X}", offset.unsigned_abs())
+            }
+            Instruction::LdHlSpImm8(offset) => write!(f, "LD HL,SP+${:02X}", offset),
+            Instruction::LdSpHl => write!(f, "LD SP,HL"),
+            Instruction::Di => write!(f, "DI"),
+            Instruction::Ei => write!(f, "EI"),
+            Instruction::ShiftR8(op, r8) => write!(f, "{} {}", op, r8),
+            Instruction::BitB3R8(bit, r8) => write!(f, "BIT {},{}", bit, r8),
+            Instruction::ResB3R8(bit, r8) => write!(f, "RES {},{}", bit, r8),
+            Instruction::SetB3R8(bit, r8) => write!(f, "SET {},{}", bit, r8),
+            Instruction::Illegal(opcode) => write!(f, "DB ${:02X}", opcode),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(bytes: &[u8], address: u16) -> String {
+        disassemble(bytes, address).unwrap().to_string()
+    }
+
+    #[test]
+    fn lengths_include_operands_and_prefix() {
+        assert_eq!(disassemble(&[0x00], 0).unwrap().length, 1);
+        assert_eq!(disassemble(&[0x3E, 0x42], 0).unwrap().length, 2);
+        assert_eq!(disassemble(&[0xC3, 0x50, 0x01], 0).unwrap().length, 3);
+        assert_eq!(disassemble(&[0xCB, 0x7C], 0).unwrap().length, 2);
+    }
+
+    #[test]
+    fn immediate_operands() {
+        assert_eq!(text(&[0x3E, 0x42], 0), "LD A,$42");
+        assert_eq!(text(&[0x21, 0x34, 0x12], 0), "LD HL,$1234");
+        assert_eq!(text(&[0xEA, 0x00, 0xC0], 0), "LD [$C000],A");
+        assert_eq!(text(&[0xE0, 0x40], 0), "LDH [$FF40],A");
+        assert_eq!(text(&[0xE8, 0xFE], 0), "ADD SP,-$02");
+        assert_eq!(text(&[0xF8, 0x05], 0), "LD HL,SP+$05");
+    }
+
+    #[test]
+    fn relative_jumps_show_the_target() {
+        assert_eq!(text(&[0x18, 0xFE], 0x0150), "JR $0150");
+        assert_eq!(text(&[0x20, 0x10], 0x0150), "JR NZ,$0162");
+    }
+
+    #[test]
+    fn prefixed_opcodes() {
+        assert_eq!(text(&[0xCB, 0x7C], 0), "BIT 7,H");
+        assert_eq!(text(&[0xCB, 0x37], 0), "SWAP A");
+        assert_eq!(text(&[0xCB, 0x86], 0), "RES 0,[HL]");
+        assert_eq!(text(&[0xCB, 0xFF], 0), "SET 7,A");
+    }
+
+    #[test]
+    fn illegal_opcodes_are_data() {
+        assert_eq!(text(&[0xD3], 0), "DB $D3");
+    }
+
+    #[test]
+    fn truncated_input_decodes_nothing() {
+        assert_eq!(disassemble(&[], 0), None);
+        assert_eq!(disassemble(&[0x3E], 0), None);
+        assert_eq!(disassemble(&[0xC3, 0x50], 0), None);
+        assert_eq!(disassemble(&[0xCB], 0), None);
+    }
+}
@@ -1,6 +1,8 @@
+use crate::bus::Bus;
+use crate::cpu::decoder::{Cond, Instruction, R8};
+use crate::cpu::registers::{Flags, Registers};
 use crate::cpu::CPU;
-use crate::error;
-use crate::mmu::MMU;
+use crate::error::EmuError;
 
 mod alu;
 mod block_0;
@@ -10,32 +12,93 @@ mod block_3;
 mod loader;
 mod prefix;
 
-pub const R16_MASK: u8 = 0b00110000;
-pub const R16_SHIFT: u8 = 4;
-pub const R8_MASK: u8 = 0b00111000;
-pub const R8_SHIFT: u8 = 3;
-
-const BLOCK_MASK: u8 = 0b11000000;
-
-const BLOCK_0_CODE: u8 = 0b00000000;
-const BLOCK_1_CODE: u8 = 0b01000000;
-const BLOCK_2_CODE: u8 = 0b10000000;
-const BLOCK_3_CODE: u8 = 0b11000000;
-
-fn get_r16_code(opcode: u8) -> u8 {
-    (opcode & R16_MASK) >> R16_SHIFT
+/// Picks the M-cycle cost of an instruction depending on whether its r8
+/// operand is a register or the `(HL)` memory operand.
+fn r8_cycles(r8: R8, register_cycles: u8, memory_cycles: u8) -> u8 {
+    match r8 {
+        R8::HLMem => memory_cycles,
+        _ => register_cycles,
+    }
 }
 
-fn get_r8_code(opcode: u8) -> u8 {
-    (opcode & R8_MASK) >> R8_SHIFT
+fn check_cc(cond: Cond, registers: &Registers) -> bool {
+    match cond {
+        Cond::NZ => !registers.get_flag(Flags::Z),
+        Cond::Z => registers.get_flag(Flags::Z),
+        Cond::NC => !registers.get_flag(Flags::C),
+        Cond::C => registers.get_flag(Flags::C),
+    }
 }
 
-pub fn execute(opcode: u8, cpu: &mut CPU, mmu: &mut MMU) -> Result<(), std::io::Error> {
-    match opcode & BLOCK_MASK {
-        BLOCK_0_CODE => block_0::execute(opcode, cpu, mmu),
-        BLOCK_1_CODE => block_1::execute(opcode, &mut cpu.registers, mmu),
-        BLOCK_2_CODE => block_2::execute(opcode, &mut cpu.registers, mmu),
-        BLOCK_3_CODE => block_3::execute(opcode, cpu, mmu),
-        _ => Err(error::invalid_opcode()),
+/// Executes a decoded instruction and returns the number of M-cycles it
+/// took, opcode and immediate fetches included.
+pub fn execute(
+    instruction: Instruction,
+    cpu: &mut CPU,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    let registers = &mut cpu.registers;
+    match instruction {
+        Instruction::Nop => Ok(1),
+        Instruction::LdR16Imm16(r16, imm16) => Ok(block_0::ld_r16_imm16(r16, imm16, registers)),
+        Instruction::LdR16MemA(r16_mem) => block_0::ld_r16mem_a(r16_mem, registers, bus),
+        Instruction::LdAR16Mem(r16_mem) => block_0::ld_a_r16mem(r16_mem, registers, bus),
+        Instruction::LdImm16MemSp(imm16) => block_0::ld_imm16mem_sp(imm16, registers, bus),
+        Instruction::IncR16(r16) => Ok(block_0::inc_r16(r16, registers)),
+        Instruction::DecR16(r16) => Ok(block_0::dec_r16(r16, registers)),
+        Instruction::AddHlR16(r16) => Ok(block_0::add_hl_r16(r16, registers)),
+        Instruction::IncR8(r8) => block_0::inc_r8(r8, registers, bus),
+        Instruction::DecR8(r8) => block_0::dec_r8(r8, registers, bus),
+        Instruction::LdR8Imm8(r8, imm8) => block_0::ld_r8_imm8(r8, imm8, registers, bus),
+        Instruction::Rlca => Ok(block_0::rlca(registers)),
+        Instruction::Rrca => Ok(block_0::rrca(registers)),
+        Instruction::Rla => Ok(block_0::rla(registers)),
+        Instruction::Rra => Ok(block_0::rra(registers)),
+        Instruction::Daa => Ok(block_0::daa(registers)),
+        Instruction::Cpl => Ok(block_0::cpl(registers)),
+        Instruction::Scf => Ok(block_0::scf(registers)),
+        Instruction::Ccf => Ok(block_0::ccf(registers)),
+        Instruction::JrImm8(relative) => Ok(block_0::jr_cc_imm8(relative, true, registers)),
+        Instruction::JrCcImm8(cond, relative) => {
+            let cc = check_cc(cond, registers);
+            Ok(block_0::jr_cc_imm8(relative, cc, registers))
+        }
+        Instruction::Stop => Ok(block_0::stop(cpu, bus)),
+        Instruction::LdR8R8(dst, src) => block_1::ld_r8_r8(dst, src, registers, bus),
+        Instruction::Halt => block_1::halt(cpu, bus),
+        Instruction::AluR8(op, r8) => block_2::alu_r8(op, r8, registers, bus),
+        Instruction::AluImm8(op, imm8) => Ok(block_3::alu_imm8(op, imm8, registers)),
+        Instruction::RetCc(cond) => block_3::ret_cc(cond, registers, bus),
+        Instruction::Ret => block_3::ret(registers, bus),
+        Instruction::Reti => block_3::reti(cpu, bus),
+        Instruction::JpCcImm16(cond, imm16) => Ok(block_3::jp_cc_imm16(cond, imm16, registers)),
+        Instruction::JpImm16(imm16) => Ok(block_3::jp_imm16(imm16, registers)),
+        Instruction::JpHl => Ok(block_3::jp_hl(registers)),
+        Instruction::CallCcImm16(cond, imm16) => {
+            block_3::call_cc_imm16(cond, imm16, registers, bus)
+        }
+        Instruction::CallImm16(imm16) => block_3::call_imm16(imm16, registers, bus),
+        Instruction::RstTgt3(target) => block_3::rst_tgt3(target, registers, bus),
+        Instruction::PopR16Stk(r16_stk) => block_3::pop_r16stk(r16_stk, registers, bus),
+        Instruction::PushR16Stk(r16_stk) => block_3::push_r16stk(r16_stk, registers, bus),
+        Instruction::LdhCMemA => block_3::ldh_cmem_a(registers, bus),
+        Instruction::LdhImm8A(imm8) => block_3::ldh_imm8_a(imm8, registers, bus),
+        Instruction::LdImm16A(imm16) => block_3::ld_imm16_a(imm16, registers, bus),
+        Instruction::LdhACMem => block_3::ldh_a_cmem(registers, bus),
+        Instruction::LdhAImm8(imm8) => block_3::ldh_a_imm8(imm8, registers, bus),
+        Instruction::LdAImm16(imm16) => block_3::ld_a_imm16(imm16, registers, bus),
+        Instruction::AddSpImm8(value) => Ok(block_3::add_sp_imm8(value, registers)),
+        Instruction::LdHlSpImm8(value) => Ok(block_3::ld_hl_sp_imm8(value, registers)),
+        Instruction::LdSpHl => Ok(block_3::ld_sp_hl(registers)),
+        Instruction::Di => Ok(block_3::di(cpu)),
+        Instruction::Ei => Ok(block_3::ei(cpu)),
+        Instruction::ShiftR8(op, r8) => prefix::shift_r8(op, r8, registers, bus),
+        Instruction::BitB3R8(bit, r8) => prefix::bit_b3_r8(bit, r8, registers, bus),
+        Instruction::ResB3R8(bit, r8) => prefix::res_b3_r8(bit, r8, registers, bus),
+        Instruction::SetB3R8(bit, r8) => prefix::set_b3_r8(bit, r8, registers, bus),
+        Instruction::Illegal(opcode) => Err(EmuError::UnsupportedOpcode {
+            opcode,
+            pc: cpu.registers.pc.wrapping_sub(1),
+        }),
     }
 }
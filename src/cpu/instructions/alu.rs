@@ -1,109 +1,73 @@
-use std::io;
+use crate::error::EmuError;
 
+use crate::bus::Bus;
+use crate::cpu::decoder::{AluOp, R8};
 use crate::cpu::registers::Flags;
 use crate::cpu::registers::Registers;
-use crate::error;
-use crate::mmu::MMU;
-
-const OPERATION_MASK: u8 = 0b00111000;
-const OPERATION_SHIFT: u8 = 3;
-const REGISTER_MASK: u8 = 0b00000111;
-const OPERATION_TYPE_MASK: u8 = 0b100;
-const NEGATION_MASK: u8 = 0b010;
-const CARRY_FLAG_MASK: u8 = 0b001;
-const SUM_OPERATION: u8 = 0b000;
-const BITWISE_OPERATION: u8 = 0b100;
-const AND_OPERATION: u8 = 0b100;
-const XOR_OPERATION: u8 = 0b101;
-const OR_OPERATION: u8 = 0b110;
-const CP_OPERATION: u8 = 0b111;
-
-pub fn alu(opcode: u8, operand: u8, registers: &mut Registers) -> Result<(), io::Error> {
-    let operation = (opcode & OPERATION_MASK) >> OPERATION_SHIFT;
 
+pub fn alu(op: AluOp, operand: u8, registers: &mut Registers) {
+    let carry = registers.get_flag(Flags::C);
     registers.reset_flags();
-    let result = match operation & OPERATION_TYPE_MASK {
-        SUM_OPERATION => sum_operation(operation, operand, registers)?,
-        BITWISE_OPERATION => bitwise_operation(operation, operand, registers)?,
-        _ => return Err(error::invalid_opcode()),
+    let result = match op {
+        AluOp::Add => add(operand, false, registers),
+        AluOp::Adc => add(operand, carry, registers),
+        AluOp::Sub => sub(operand, false, registers),
+        AluOp::Sbc => sub(operand, carry, registers),
+        AluOp::And => and(operand, registers),
+        AluOp::Xor => registers.a ^ operand,
+        AluOp::Or => registers.a | operand,
+        AluOp::Cp => cp(operand, registers),
     };
-    alu_post_process(operation, result, registers)
+    alu_post_process(op, result, registers)
 }
 
-pub fn alu_register(opcode: u8, registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    let src_register = opcode & REGISTER_MASK;
-    let src_value = registers.get_word(src_register, mmu)?;
+pub fn alu_register(
+    op: AluOp,
+    r8: R8,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    let src_value = registers.get_word(r8, bus)?;
 
-    alu(opcode, src_value, registers)
+    alu(op, src_value, registers);
+    Ok(super::r8_cycles(r8, 1, 2))
 }
 
-fn alu_post_process(operation: u8, result: u8, registers: &mut Registers) -> Result<(), io::Error> {
+fn alu_post_process(op: AluOp, result: u8, registers: &mut Registers) {
     if result == 0 {
         registers.set_flags(Flags::Z, true);
     }
-    if operation != CP_OPERATION {
+    if op != AluOp::Cp {
         registers.a = result;
     }
-    Ok(())
-}
-
-fn sum_operation(operation: u8, operand: u8, registers: &mut Registers) -> Result<u8, io::Error> {
-    let carry_flag = operation & CARRY_FLAG_MASK != 0;
-    let negation = operation & NEGATION_MASK != 0;
-
-    match negation {
-        false => add(operand, carry_flag, registers),
-        true => sub(operand, carry_flag, registers),
-    }
 }
 
-fn bitwise_operation(
-    operation: u8,
-    operand: u8,
-    registers: &mut Registers,
-) -> Result<u8, io::Error> {
-    match operation {
-        AND_OPERATION => and(operand, registers),
-        XOR_OPERATION => Ok(registers.a ^ operand),
-        OR_OPERATION => Ok(registers.a | operand),
-        CP_OPERATION => cp(operand, registers),
-        _ => Err(error::invalid_opcode()),
-    }
-}
-
-fn add(operand: u8, carry: bool, registers: &mut Registers) -> Result<u8, io::Error> {
+/// `carry` is the carry flag for ADC, read before the flags are reset.
+fn add(operand: u8, carry: bool, registers: &mut Registers) -> u8 {
     let a = registers.a;
-    let (mut result, mut overflow) = a.overflowing_add(operand);
-    if carry {
-        let mut carry_overflow: bool;
-        (result, carry_overflow) = a.overflowing_add(registers.get_flag(Flags::C) as u8);
-        overflow = overflow | carry_overflow;
-    }
-    registers.set_flags(Flags::C, overflow);
-    registers.set_h_flag_add(operand, a);
-    Ok(result)
+    let carry = carry as u8;
+    let result = a.wrapping_add(operand).wrapping_add(carry);
+    registers.set_flags(Flags::C, a as u16 + operand as u16 + carry as u16 > 0xFF);
+    registers.set_flags(Flags::H, (a & 0x0F) + (operand & 0x0F) + carry > 0x0F);
+    result
 }
 
-fn sub(operand: u8, carry: bool, registers: &mut Registers) -> Result<u8, io::Error> {
+/// `carry` is the carry flag for SBC, read before the flags are reset.
+fn sub(operand: u8, carry: bool, registers: &mut Registers) -> u8 {
     let a = registers.a;
-    let (mut result, mut overflow) = a.overflowing_sub(operand);
-
-    if carry {
-        let mut carry_overflow: bool;
-        (result, carry_overflow) = a.overflowing_sub(registers.get_flag(Flags::C) as u8);
-        overflow = overflow | carry_overflow;
-    }
-    registers.set_flags(Flags::C, overflow);
+    let carry = carry as u8;
+    let result = a.wrapping_sub(operand).wrapping_sub(carry);
+    registers.set_flags(Flags::C, (a as u16) < operand as u16 + carry as u16);
     registers.set_flags(Flags::N, true);
-    registers.set_h_flag_sub(a, operand);
-    Ok(result)
+    registers.set_flags(Flags::H, a & 0x0F < (operand & 0x0F) + carry);
+    result
 }
 
-fn and(operand: u8, registers: &mut Registers) -> Result<u8, io::Error> {
+fn and(operand: u8, registers: &mut Registers) -> u8 {
     registers.set_flags(Flags::H, true);
-    Ok(registers.a & operand)
+    registers.a & operand
 }
 
-fn cp(operand: u8, registers: &mut Registers) -> Result<u8, io::Error> {
+fn cp(operand: u8, registers: &mut Registers) -> u8 {
     sub(operand, false, registers)
 }
@@ -1,218 +1,187 @@
+use crate::bus::Bus;
+use crate::cpu::decoder::{Imm16, Imm8, R16Mem, R16, R8};
 use crate::cpu::registers::{Flags, Registers};
 use crate::cpu::CPU;
-use crate::error;
-use crate::mmu::MMU;
-use std::io;
-
-const INSTRUCTION_TYPE_MASK: u8 = 0b00000111;
-const EXTENDED_INSTRUCTION_TYPE_MASK: u8 = INSTRUCTION_TYPE_MASK | 0b00001000;
-
-const NOP_OPCODE: u8 = 0b00000000;
-
-const LD_R16_IMM16_OPCODE: u8 = 0b00000001;
-const LD_R16MEM_A_OPCODE: u8 = 0b00000010;
-const LD_A_R16MEM_OPCODE: u8 = 0b00001010;
-const LD_IMM16MEM_SP_OPCODE: u8 = 0b00001000;
-const INC_R8_OPCODE: u8 = 0b00000100;
-const DEC_R8_OPCODE: u8 = 0b00000101;
-const LD_R8_IMM8_OPCODE: u8 = 0b00000110;
-
-const INC_R16_OPCODE: u8 = 0b00000011;
-const DEC_R16_OPCODE: u8 = 0b00001011;
-const ADD_HL_R16_OPCODE: u8 = 0b00001001;
-
-const RLCA_OPCODE: u8 = 0b00000111;
-const RRCA_OPCODE: u8 = 0b00001111;
-const RLA_OPCODE: u8 = 0b00010111;
-const RRA_OPCODE: u8 = 0b00011111;
-const DAA_OPCODE: u8 = 0b00100111;
-const CPL_OPCODE: u8 = 0b00101111;
-const SCF_OPCODE: u8 = 0b00110111;
-const CCF_OPCODE: u8 = 0b00111111;
-
-const JR_IMM8_OPCODE: u8 = 0b00011000;
-const JR_NZ_IMM8_OPCODE: u8 = 0b00100000;
-const JR_Z_IMM8_OPCODE: u8 = 0b00101000;
-const JR_NC_IMM8_OPCODE: u8 = 0b00110000;
-const JR_C_IMM8_OPCODE: u8 = 0b00111000;
-
-pub fn execute(opcode: u8, cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    match opcode & INSTRUCTION_TYPE_MASK {
-        INC_R8_OPCODE => return inc_r8(opcode, &mut cpu.registers, mmu),
-        DEC_R8_OPCODE => return dec_r8(opcode, &mut cpu.registers, mmu),
-        LD_R8_IMM8_OPCODE => return ld_r8_imm8(opcode, cpu, mmu),
-        _ => {}
-    };
-    match opcode & EXTENDED_INSTRUCTION_TYPE_MASK {
-        LD_R16_IMM16_OPCODE => return ld_r16_imm16(opcode, cpu, mmu),
-        LD_R16MEM_A_OPCODE => return ld_r16mem_a(opcode, &mut cpu.registers, mmu),
-        LD_A_R16MEM_OPCODE => return ld_a_r16mem(opcode, &mut cpu.registers, mmu),
-        INC_R16_OPCODE => return inc_r16(opcode, &mut cpu.registers),
-        DEC_R16_OPCODE => return dec_r16(opcode, &mut cpu.registers),
-        ADD_HL_R16_OPCODE => return add_hl_r16(opcode, &mut cpu.registers),
-        _ => {}
-    };
-    match opcode {
-        NOP_OPCODE => return Ok(()),
-        RLCA_OPCODE => rlca(&mut cpu.registers),
-        RRCA_OPCODE => rrca(&mut cpu.registers),
-        RLA_OPCODE => rla(&mut cpu.registers),
-        RRA_OPCODE => rra(&mut cpu.registers),
-        DAA_OPCODE => daa(&mut cpu.registers),
-        CPL_OPCODE => cpl(&mut cpu.registers),
-        SCF_OPCODE => scf(&mut cpu.registers),
-        CCF_OPCODE => ccf(&mut cpu.registers),
-        JR_IMM8_OPCODE => return jr_imm8(cpu, mmu),
-        LD_IMM16MEM_SP_OPCODE => return ld_imm16mem_sp(cpu, mmu),
-        JR_NZ_IMM8_OPCODE => return jr_cc_imm8(cpu, mmu, !cpu.registers.get_flag(Flags::Z)),
-        JR_Z_IMM8_OPCODE => return jr_cc_imm8(cpu, mmu, cpu.registers.get_flag(Flags::Z)),
-        JR_NC_IMM8_OPCODE => return jr_cc_imm8(cpu, mmu, !cpu.registers.get_flag(Flags::C)),
-        JR_C_IMM8_OPCODE => return jr_cc_imm8(cpu, mmu, cpu.registers.get_flag(Flags::C)),
-        _ => return Err(error::unsupported_instruction()),
-    }
-    Ok(())
-}
+use crate::error::EmuError;
 
-fn inc_r16(opcode: u8, registers: &mut Registers) -> Result<(), io::Error> {
-    let register = super::get_r16_code(opcode);
-    let register_value = registers.get_dword(register)?;
-    registers.set_dword(register, register_value.wrapping_add(1))
+pub fn inc_r16(r16: R16, registers: &mut Registers) -> u8 {
+    let register_value = registers.get_dword(r16);
+    registers.set_dword(r16, register_value.wrapping_add(1));
+    2
 }
 
-fn dec_r16(opcode: u8, registers: &mut Registers) -> Result<(), io::Error> {
-    let register = super::get_r16_code(opcode);
-    let register_value = registers.get_dword(register)?;
-    registers.set_dword(register, register_value.wrapping_sub(1))
+pub fn dec_r16(r16: R16, registers: &mut Registers) -> u8 {
+    let register_value = registers.get_dword(r16);
+    registers.set_dword(r16, register_value.wrapping_sub(1));
+    2
 }
 
-fn add_hl_r16(opcode: u8, registers: &mut Registers) -> Result<(), io::Error> {
-    let register = super::get_r16_code(opcode);
-    let register_value = registers.get_dword(register)?;
-    registers.set_hl(registers.get_hl() + register_value);
-    Ok(())
+/// Z is left untouched; H and C come from bits 11 and 15.
+pub fn add_hl_r16(r16: R16, registers: &mut Registers) -> u8 {
+    let register_value = registers.get_dword(r16);
+    let hl = registers.get_hl();
+    let (result, overflow) = hl.overflowing_add(register_value);
+    registers.set_flags(Flags::N, false);
+    registers.set_flags(Flags::H, (hl & 0x0FFF) + (register_value & 0x0FFF) > 0x0FFF);
+    registers.set_flags(Flags::C, overflow);
+    registers.set_hl(result);
+    2
 }
 
-fn inc_r8(opcode: u8, registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    let register = super::get_r8_code(opcode);
-    let mut register_value = registers.get_word(register, mmu)?;
+pub fn inc_r8(r8: R8, registers: &mut Registers, bus: &mut impl Bus) -> Result<u8, EmuError> {
+    let mut register_value = registers.get_word(r8, bus)?;
 
     registers.set_h_flag_add(register_value, 1);
     register_value = register_value.wrapping_add(1);
     registers.set_flags(Flags::Z, register_value == 0);
     registers.set_flags(Flags::N, false);
-    registers.set_word(register, register_value, mmu)
+    registers.set_word(r8, register_value, bus)?;
+    Ok(super::r8_cycles(r8, 1, 3))
 }
 
-fn dec_r8(opcode: u8, registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    let register = super::get_r8_code(opcode);
-    let mut register_value = registers.get_word(register, mmu)?;
+pub fn dec_r8(r8: R8, registers: &mut Registers, bus: &mut impl Bus) -> Result<u8, EmuError> {
+    let mut register_value = registers.get_word(r8, bus)?;
 
     registers.set_h_flag_sub(register_value, 1);
     register_value = register_value.wrapping_sub(1);
     registers.set_flags(Flags::Z, register_value == 0);
     registers.set_flags(Flags::N, true);
-    registers.set_word(register, register_value, mmu)
+    registers.set_word(r8, register_value, bus)?;
+    Ok(super::r8_cycles(r8, 1, 3))
+}
+
+pub fn ld_r16mem_a(
+    r16_mem: R16Mem,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    bus.set_word(registers.get_dword_mem(r16_mem) as usize, registers.a)?;
+    Ok(2)
 }
 
-fn ld_r16mem_a(opcode: u8, registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    let r16_code = super::get_r16_code(opcode);
-    mmu.set_word(registers.get_dword_mem(r16_code)? as usize, registers.a)
+pub fn ld_a_r16mem(
+    r16_mem: R16Mem,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    let word_address = registers.get_dword_mem(r16_mem) as usize;
+    registers.a = bus.get_word(word_address)?;
+    Ok(2)
 }
 
-fn ld_a_r16mem(opcode: u8, registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    let r16_code = super::get_r16_code(opcode);
-    let word_address = registers.get_dword(r16_code)? as usize;
-    registers.a = mmu.get_word(word_address)?;
-    Ok(())
+pub fn ld_r16_imm16(r16: R16, imm16: Imm16, registers: &mut Registers) -> u8 {
+    registers.set_dword(r16, imm16);
+    3
 }
 
-fn ld_r16_imm16(opcode: u8, cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let register = super::get_r16_code(opcode);
-    let imm16 = cpu.fetch_next_dword(mmu)?;
-    cpu.registers.set_dword(register, imm16)
+pub fn ld_r8_imm8(
+    r8: R8,
+    imm8: Imm8,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    registers.set_word(r8, imm8, bus)?;
+    Ok(super::r8_cycles(r8, 2, 3))
 }
 
-fn ld_r8_imm8(opcode: u8, cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let register = super::get_r8_code(opcode);
-    let imm8 = cpu.fetch_next_word(mmu)?;
-    cpu.registers.set_word(register, imm8, mmu)
+pub fn ld_imm16mem_sp(
+    imm16: Imm16,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    bus.set_dword(imm16 as usize, registers.sp)?;
+    Ok(5)
 }
 
-fn ld_imm16mem_sp(cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let address = cpu.fetch_next_dword(mmu)?;
-    mmu.set_dword(address as usize, cpu.registers.sp)
+pub fn stop(cpu: &mut CPU, bus: &mut impl Bus) -> u8 {
+    cpu.stop(bus);
+    1
 }
 
-fn rlca(registers: &mut Registers) {
+/// Unlike their CB-prefixed versions, the accumulator rotations always
+/// clear Z.
+pub fn rlca(registers: &mut Registers) -> u8 {
+    registers.reset_flags();
     registers.set_flags(Flags::C, registers.a & 0b10000000 != 0);
-    registers.a <<= 1;
+    registers.a = registers.a.rotate_left(1);
+    1
 }
 
-fn rrca(registers: &mut Registers) {
+pub fn rrca(registers: &mut Registers) -> u8 {
+    registers.reset_flags();
     registers.set_flags(Flags::C, registers.a & 0b00000001 != 0);
-    registers.a >>= 1;
+    registers.a = registers.a.rotate_right(1);
+    1
 }
 
-fn rla(registers: &mut Registers) {
+pub fn rla(registers: &mut Registers) -> u8 {
     let carry = registers.get_flag(Flags::C) as u8;
+    registers.reset_flags();
     registers.set_flags(Flags::C, registers.a & 0b10000000 != 0);
     registers.a = (registers.a << 1) | carry;
+    1
 }
 
-fn rra(registers: &mut Registers) {
+pub fn rra(registers: &mut Registers) -> u8 {
     let carry = registers.get_flag(Flags::C) as u8;
+    registers.reset_flags();
     registers.set_flags(Flags::C, registers.a & 0b00000001 != 0);
     registers.a = (registers.a >> 1) | (carry << 7);
+    1
 }
 
-fn daa(registers: &mut Registers) {
+pub fn daa(registers: &mut Registers) -> u8 {
     let op = match registers.get_flag(Flags::N) {
-        true => |a: u8, correction: u8| a - correction,
-        false => |a: u8, correction: u8| a + correction,
+        true => u8::wrapping_sub,
+        false => u8::wrapping_add,
     };
 
-    if registers.get_flag(Flags::H) || registers.a & 0xF > 9 {
-        registers.a = op(registers.a, 0x6);
+    // After an addition, digits above 9 are adjusted too; after a
+    // subtraction, only the borrows recorded by H and C are.
+    let subtraction = registers.get_flag(Flags::N);
+    let mut adjustment = 0;
+    let mut carry = registers.get_flag(Flags::C);
+    if registers.get_flag(Flags::H) || (!subtraction && registers.a & 0xF > 9) {
+        adjustment |= 0x06;
     }
-    if registers.get_flag(Flags::C) || (registers.a & 0xF0) >> 4 > 9 {
-        registers.a = op(registers.a, 0x60);
+    if carry || (!subtraction && registers.a > 0x99) {
+        adjustment |= 0x60;
+        carry = true;
     }
+    registers.a = op(registers.a, adjustment);
 
     registers.set_flags(Flags::Z, registers.a == 0);
     // N is left untouched
     registers.set_flags(Flags::H, false);
-    registers.set_flags(Flags::C, (registers.a & 0xF0) >> 4 > 9);
+    registers.set_flags(Flags::C, carry);
+    1
 }
 
-fn cpl(registers: &mut Registers) {
+pub fn cpl(registers: &mut Registers) -> u8 {
     registers.set_flags(Flags::N, true);
     registers.set_flags(Flags::H, true);
     registers.a = !registers.a;
+    1
 }
 
-fn scf(registers: &mut Registers) {
+pub fn scf(registers: &mut Registers) -> u8 {
     registers.set_flags(Flags::N, false);
     registers.set_flags(Flags::H, false);
     registers.set_flags(Flags::C, true);
+    1
 }
 
-fn ccf(registers: &mut Registers) {
+pub fn ccf(registers: &mut Registers) -> u8 {
+    registers.set_flags(Flags::N, false);
+    registers.set_flags(Flags::H, false);
     registers.set_flags(Flags::C, !registers.get_flag(Flags::C));
+    1
 }
 
-fn jr_imm8(cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    jr_cc_imm8(cpu, mmu, true)
-}
-
-fn jr_cc_imm8(cpu: &mut CPU, mmu: &mut MMU, cc: bool) -> Result<(), io::Error> {
-    let relative = cpu.fetch_next_word(mmu)? as i8;
-
+pub fn jr_cc_imm8(relative: i8, cc: bool, registers: &mut Registers) -> u8 {
     if !cc {
-        return Ok(());
-    } else if relative < 0 {
-        cpu.registers.pc -= -relative as u16;
-    } else {
-        cpu.registers.pc += relative as u16;
+        return 2;
     }
-    Ok(())
+    registers.pc = registers.pc.wrapping_add_signed(relative as i16);
+    3
 }
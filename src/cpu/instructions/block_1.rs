@@ -1,9 +1,21 @@
+use crate::bus::Bus;
+use crate::cpu::decoder::R8;
 use crate::cpu::instructions::loader::load;
 use crate::cpu::registers::Registers;
-use crate::mmu::MMU;
+use crate::cpu::CPU;
 
-use std::io;
+use crate::error::EmuError;
 
-pub fn execute(opcode: u8, registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    load(opcode, registers, mmu)
+pub fn ld_r8_r8(
+    dst: R8,
+    src: R8,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    load(dst, src, registers, bus)
+}
+
+pub fn halt(cpu: &mut CPU, bus: &mut impl Bus) -> Result<u8, EmuError> {
+    cpu.halt(bus)?;
+    Ok(1)
 }
@@ -1,9 +1,15 @@
-use std::io;
+use crate::error::EmuError;
 
+use crate::bus::Bus;
+use crate::cpu::decoder::{AluOp, R8};
 use crate::cpu::instructions::alu;
 use crate::cpu::registers::Registers;
-use crate::mmu::MMU;
 
-pub fn execute(opcode: u8, registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    alu::alu_register(opcode, registers, mmu)
+pub fn alu_r8(
+    op: AluOp,
+    r8: R8,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    alu::alu_register(op, r8, registers, bus)
 }
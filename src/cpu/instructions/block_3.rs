@@ -1,271 +1,210 @@
-use std::{io, mem};
+use std::mem;
 
+use crate::error::EmuError;
+
+use crate::bus::Bus;
+use crate::cpu::decoder::{AluOp, Cond, Imm16, Imm8, R16Stk};
 use crate::cpu::instructions::alu;
-use crate::cpu::instructions::prefix;
 use crate::cpu::registers::{Flags, Registers};
-use crate::cpu::{DWord, CPU};
-use crate::error;
-use crate::mmu::MMU;
+use crate::cpu::{DWord, CPU, EI_DELAY};
 
 const LDH_ADDRESS_START: usize = 0xff00;
 
-const INSTRUCTION_TYPE_MASK: u8 = 0b00000111;
-const CC_INSTRUCTION_TYPE_MASK: u8 = 0b00100111;
-const STK_INSTRUCTION_TYPE_MASK: u8 = 0b00001111;
-
-const RET_CC_OPCODE: u8 = 0b000;
-const JP_CC_IMM16_OPCODE: u8 = 0b010;
-const CALL_CC_IMM16_OPCODE: u8 = 0b100;
-const ALU_OPCODE: u8 = 0b110;
-const RST_OPCODE: u8 = 0b111;
-
-const POP_OPCODE: u8 = 0b0001;
-const PUSH_OPCODE: u8 = 0b0101;
-
-const RET_OPCODE: u8 = 0b11001001;
-const RETI_OPCODE: u8 = 0b11011001;
-const JP_IMM16_OPCODE: u8 = 0b11000011;
-const JP_HL_OPCODE: u8 = 0b11101001;
-const CALL_IMM16_OPCODE: u8 = 0b11001101;
-
-const PREFIX_OPCODE: u8 = 0b11001011;
-
-const LDH_CMEM_A_OPCODE: u8 = 0b11100010;
-const LDH_IMM8_A_OPCODE: u8 = 0b11100000;
-const LD_IMM16_A_OPCODE: u8 = 0b11101010;
-const LDH_A_CMEM_OPCODE: u8 = 0b11110010;
-const LDH_A_IMM8_OPCODE: u8 = 0b11110000;
-const LD_A_IMM16_OPCODE: u8 = 0b11111010;
-
-const ADD_SP_IMM8_OPCODE: u8 = 0b11101000;
-const LD_HL_SP_IMM8_OPCODE: u8 = 0b11111000;
-const LD_SP_HL_OPCODE: u8 = 0b11111001;
-
-const DI_OPCODE: u8 = 0b11110011;
-const EI_OPCODE: u8 = 0b11111011;
-
-const CC_MASK: u8 = 0b00011000;
-const CC_SHIFT: u8 = 3;
-
-const NZ_CC: u8 = 0;
-const Z_CC: u8 = 1;
-const NC_CC: u8 = 2;
-const C_CC: u8 = 3;
-
-const TGT_MASK: u8 = 0b00111000;
-
-pub fn execute(opcode: u8, cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let registers = &mut cpu.registers;
-    match opcode & INSTRUCTION_TYPE_MASK {
-        ALU_OPCODE => return alu_imm8(opcode, cpu, mmu),
-        RST_OPCODE => return rst_tgt3(opcode, registers, mmu),
-        _ => {}
-    }
-    match opcode & CC_INSTRUCTION_TYPE_MASK {
-        RET_CC_OPCODE => return ret_cc(opcode, registers, mmu),
-        JP_CC_IMM16_OPCODE => return jp_cc_imm16(opcode, cpu, mmu),
-        CALL_CC_IMM16_OPCODE => return call_cc_imm16(opcode, cpu, mmu),
-        _ => {}
-    };
-    match opcode & STK_INSTRUCTION_TYPE_MASK {
-        POP_OPCODE => return pop_r16stk(opcode, registers, mmu),
-        PUSH_OPCODE => return push_r16stk(opcode, registers, mmu),
-        _ => {}
-    };
-    match opcode {
-        RET_OPCODE => return ret(registers, mmu),
-        RETI_OPCODE => reti(cpu, mmu)?,
-        JP_IMM16_OPCODE => return jp_imm16(cpu, mmu),
-        JP_HL_OPCODE => jp_hl(cpu),
-        CALL_IMM16_OPCODE => return call_imm16(cpu, mmu),
-
-        PREFIX_OPCODE => return prefix(cpu, mmu),
-        LDH_CMEM_A_OPCODE => return ldh_cmem_a(registers, mmu),
-        LDH_IMM8_A_OPCODE => return ldh_imm8_a(cpu, mmu),
-        LD_IMM16_A_OPCODE => return ld_imm16_a(cpu, mmu),
-        LDH_A_CMEM_OPCODE => return ldh_a_cmem(registers, mmu),
-        LDH_A_IMM8_OPCODE => return ldh_a_imm8(cpu, mmu),
-        LD_A_IMM16_OPCODE => return ld_a_imm16(cpu, mmu),
-
-        ADD_SP_IMM8_OPCODE => return add_sp_imm8(cpu, mmu),
-        LD_HL_SP_IMM8_OPCODE => return ld_hl_sp_imm8(cpu, mmu),
-        LD_SP_HL_OPCODE => ld_sp_hl(registers),
-
-        DI_OPCODE => di(cpu),
-        EI_OPCODE => ei(cpu),
-        _ => return Err(error::invalid_opcode()),
-    };
-    Ok(())
-}
-
-fn alu_imm8(opcode: u8, cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    alu::alu(opcode, cpu.fetch_next_word(mmu)?, &mut cpu.registers)
-}
-
-fn prefix(cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let opcode = cpu.fetch_next_word(mmu)?;
-    prefix::execute(opcode, cpu, mmu)
+pub fn alu_imm8(op: AluOp, imm8: Imm8, registers: &mut Registers) -> u8 {
+    alu::alu(op, imm8, registers);
+    2
 }
 
-fn push(value: u16, registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    registers.sp -= mem::size_of::<DWord>() as u16;
-    mmu.set_dword(registers.sp as usize, value)
+fn push(value: u16, registers: &mut Registers, bus: &mut impl Bus) -> Result<(), EmuError> {
+    registers.sp = registers.sp.wrapping_sub(mem::size_of::<DWord>() as u16);
+    bus.set_dword(registers.sp as usize, value)
 }
 
-fn pop(registers: &mut Registers, mmu: &mut MMU) -> Result<u16, io::Error> {
-    let result = mmu.get_dword(registers.sp as usize)?;
-    registers.sp += mem::size_of::<DWord>() as u16;
+fn pop(registers: &mut Registers, bus: &mut impl Bus) -> Result<u16, EmuError> {
+    let result = bus.get_dword(registers.sp as usize)?;
+    registers.sp = registers.sp.wrapping_add(mem::size_of::<DWord>() as u16);
     Ok(result)
 }
 
-fn push_r16stk(opcode: u8, registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    let register = super::get_r16_code(opcode);
-    let value = registers.get_dword_stk(register)?;
-    push(value, registers, mmu)
+pub fn push_r16stk(
+    r16_stk: R16Stk,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    let value = registers.get_dword_stk(r16_stk);
+    push(value, registers, bus)?;
+    Ok(4)
 }
 
-fn pop_r16stk(opcode: u8, registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    let register = super::get_r16_code(opcode);
-    let value: u16 = pop(registers, mmu)?;
-    registers.set_dword_stk(register, value)
+pub fn pop_r16stk(
+    r16_stk: R16Stk,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    let value: u16 = pop(registers, bus)?;
+    registers.set_dword_stk(r16_stk, value);
+    Ok(3)
 }
 
-fn ret(registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    let value = pop(registers, mmu)?;
+pub fn ret(registers: &mut Registers, bus: &mut impl Bus) -> Result<u8, EmuError> {
+    let value = pop(registers, bus)?;
     registers.pc = value;
-    Ok(())
+    Ok(4)
 }
 
-fn reti(cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
+pub fn reti(cpu: &mut CPU, bus: &mut impl Bus) -> Result<u8, EmuError> {
     cpu.ime = true;
-    ret(&mut cpu.registers, mmu)
+    cpu.ei_delay = 0;
+    ret(&mut cpu.registers, bus)
 }
 
-fn ret_cc(opcode: u8, registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    if check_cc(opcode, registers)? {
-        ret(registers, mmu)?;
+pub fn ret_cc(cond: Cond, registers: &mut Registers, bus: &mut impl Bus) -> Result<u8, EmuError> {
+    if super::check_cc(cond, registers) {
+        ret(registers, bus)?;
+        return Ok(5);
     }
-    Ok(())
+    Ok(2)
 }
 
-fn check_cc(opcode: u8, registers: &mut Registers) -> Result<bool, io::Error> {
-    match (opcode & CC_MASK) >> CC_SHIFT {
-        NZ_CC => Ok(!registers.get_flag(Flags::Z)),
-        Z_CC => Ok(registers.get_flag(Flags::Z)),
-        NC_CC => Ok(!registers.get_flag(Flags::C)),
-        C_CC => Ok(registers.get_flag(Flags::C)),
-        _ => Err(error::invalid_condition_code()),
-    }
+pub fn jp_imm16(imm16: Imm16, registers: &mut Registers) -> u8 {
+    registers.pc = imm16;
+    4
 }
 
-fn jp_imm16(cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let imm16 = cpu.fetch_next_dword(mmu)?;
-    cpu.registers.pc = imm16;
-    Ok(())
-}
-
-fn jp_cc_imm16(opcode: u8, cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let imm16 = cpu.fetch_next_dword(mmu)?;
-
-    if check_cc(opcode, &mut cpu.registers)? {
-        cpu.registers.pc = imm16;
+pub fn jp_cc_imm16(cond: Cond, imm16: Imm16, registers: &mut Registers) -> u8 {
+    if super::check_cc(cond, registers) {
+        registers.pc = imm16;
+        return 4;
     }
-    Ok(())
+    3
 }
 
-fn jp_hl(cpu: &mut CPU) {
-    let hl = cpu.registers.get_hl();
-    cpu.registers.pc = hl;
+pub fn jp_hl(registers: &mut Registers) -> u8 {
+    registers.pc = registers.get_hl();
+    1
 }
 
-fn call(fn_address: u16, registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    push(registers.pc, registers, mmu)?;
+fn call(fn_address: u16, registers: &mut Registers, bus: &mut impl Bus) -> Result<(), EmuError> {
+    push(registers.pc, registers, bus)?;
 
     registers.pc = fn_address;
 
     Ok(())
 }
 
-fn call_imm16(cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let imm16 = cpu.fetch_next_dword(mmu)?;
-
-    call(imm16, &mut cpu.registers, mmu)
-}
-
-fn call_cc_imm16(opcode: u8, cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let imm16 = cpu.fetch_next_dword(mmu)?;
-
-    if check_cc(opcode, &mut cpu.registers)? {
-        call(imm16, &mut cpu.registers, mmu)?;
+pub fn call_imm16(
+    imm16: Imm16,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    call(imm16, registers, bus)?;
+    Ok(6)
+}
+
+pub fn call_cc_imm16(
+    cond: Cond,
+    imm16: Imm16,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    if super::check_cc(cond, registers) {
+        call(imm16, registers, bus)?;
+        return Ok(6);
     }
-    Ok(())
+    Ok(3)
 }
 
-fn rst_tgt3(opcode: u8, registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
-    let target = opcode & TGT_MASK;
-
-    call(target as u16, registers, mmu)
+pub fn rst_tgt3(target: u8, registers: &mut Registers, bus: &mut impl Bus) -> Result<u8, EmuError> {
+    call(target as u16, registers, bus)?;
+    Ok(4)
 }
 
-fn ldh_cmem_a(registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
+pub fn ldh_cmem_a(registers: &mut Registers, bus: &mut impl Bus) -> Result<u8, EmuError> {
     let address = LDH_ADDRESS_START + registers.c as usize;
-    mmu.set_word(address, registers.a)
+    bus.set_word(address, registers.a)?;
+    Ok(2)
 }
 
-fn ldh_a_cmem(registers: &mut Registers, mmu: &mut MMU) -> Result<(), io::Error> {
+pub fn ldh_a_cmem(registers: &mut Registers, bus: &mut impl Bus) -> Result<u8, EmuError> {
     let address = LDH_ADDRESS_START + registers.c as usize;
-    registers.a = mmu.get_word(address)?;
-    Ok(())
+    registers.a = bus.get_word(address)?;
+    Ok(2)
 }
 
-fn ldh_imm8_a(cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let address = LDH_ADDRESS_START + cpu.fetch_next_word(mmu)? as usize;
-    mmu.set_word(address, cpu.registers.a)
+pub fn ldh_imm8_a(
+    imm8: Imm8,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    let address = LDH_ADDRESS_START + imm8 as usize;
+    bus.set_word(address, registers.a)?;
+    Ok(3)
 }
 
-fn ldh_a_imm8(cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let address = LDH_ADDRESS_START + cpu.fetch_next_word(mmu)? as usize;
-    cpu.registers.a = mmu.get_word(address)?;
-    Ok(())
+pub fn ldh_a_imm8(
+    imm8: Imm8,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    let address = LDH_ADDRESS_START + imm8 as usize;
+    registers.a = bus.get_word(address)?;
+    Ok(3)
 }
 
-fn ld_imm16_a(cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let address = cpu.fetch_next_dword(mmu)?;
-    mmu.set_word(address as usize, cpu.registers.a)
+pub fn ld_imm16_a(
+    imm16: Imm16,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    bus.set_word(imm16 as usize, registers.a)?;
+    Ok(4)
 }
 
-fn ld_a_imm16(cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let address = cpu.fetch_next_dword(mmu)?;
-    cpu.registers.a = mmu.get_word(address as usize)?;
-    Ok(())
+pub fn ld_a_imm16(
+    imm16: Imm16,
+    registers: &mut Registers,
+    bus: &mut impl Bus,
+) -> Result<u8, EmuError> {
+    registers.a = bus.get_word(imm16 as usize)?;
+    Ok(4)
 }
 
-fn add_sp_imm8(cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    let value = cpu.fetch_next_word(mmu)?;
-
-    cpu.registers.reset_flags();
-    let (result, overflow) = cpu.registers.sp.overflowing_add(value as u16);
-    cpu.registers.set_flags(Flags::C, overflow);
-    cpu.registers.set_h_flag_add(cpu.registers.sp as u8, value);
-    cpu.registers.sp = result;
-    Ok(())
+/// SP plus a signed offset. H and C come from the unsigned addition of
+/// the offset to the low byte of SP; Z and N are cleared.
+fn sp_plus_imm8(value: i8, registers: &mut Registers) -> u16 {
+    let sp = registers.sp;
+    let offset = value as u8;
+    registers.reset_flags();
+    registers.set_flags(Flags::C, (sp & 0xFF) + offset as u16 > 0xFF);
+    registers.set_h_flag_add(sp as u8, offset);
+    sp.wrapping_add_signed(value as i16)
 }
 
-fn ld_hl_sp_imm8(cpu: &mut CPU, mmu: &mut MMU) -> Result<(), io::Error> {
-    add_sp_imm8(cpu, mmu)?;
+pub fn add_sp_imm8(value: i8, registers: &mut Registers) -> u8 {
+    registers.sp = sp_plus_imm8(value, registers);
+    4
+}
 
-    ld_sp_hl(&mut cpu.registers);
-    Ok(())
+pub fn ld_hl_sp_imm8(value: i8, registers: &mut Registers) -> u8 {
+    let result = sp_plus_imm8(value, registers);
+    registers.set_hl(result);
+    3
 }
 
-fn ld_sp_hl(registers: &mut Registers) {
-    registers.set_hl(registers.sp);
+pub fn ld_sp_hl(registers: &mut Registers) -> u8 {
+    registers.sp = registers.get_hl();
+    2
 }
 
-fn di(cpu: &mut CPU) {
+pub fn di(cpu: &mut CPU) -> u8 {
     cpu.ime = false;
+    cpu.ei_delay = 0;
+    1
 }
 
-fn ei(cpu: &mut CPU) {
-    cpu.ime = true;
+pub fn ei(cpu: &mut CPU) -> u8 {
+    if !cpu.ime && cpu.ei_delay == 0 {
+        cpu.ei_delay = EI_DELAY;
+    }
+    1
 }
@@ -1,35 +1,17 @@
-use crate::error;
-use crate::mmu::MMU;
-use std::io;
-
-pub const B_REGISTER_CODE: u8 = 0x0;
-pub const C_REGISTER_CODE: u8 = 0x1;
-pub const D_REGISTER_CODE: u8 = 0x2;
-pub const E_REGISTER_CODE: u8 = 0x3;
-pub const H_REGISTER_CODE: u8 = 0x4;
-pub const L_REGISTER_CODE: u8 = 0x5;
-pub const HL_MEM_REGISTER_CODE: u8 = 0x6;
-pub const A_REGISTER_CODE: u8 = 0x7;
-pub const BC_REGISTER_CODE: u8 = 0x0;
-pub const DE_REGISTER_CODE: u8 = 0x1;
-pub const HL_REGISTER_CODE: u8 = 0x2;
-pub const SP_REGISTER_CODE: u8 = 0x3;
-pub const AF_REGISTER_CODE: u8 = 0x3;
-pub const BC_MEM_REGISTER_CODE: u8 = 0x0;
-pub const DE_MEM_REGISTER_CODE: u8 = 0x1;
-pub const HLI_MEM_REGISTER_CODE: u8 = 0x2;
-pub const HLD_MEM_REGISTER_CODE: u8 = 0x3;
+use crate::bus::Bus;
+use crate::cpu::decoder::{R16Mem, R16Stk, R16, R8};
+use crate::error::EmuError;
 
 #[derive(Debug)]
 pub struct Registers {
     pub a: u8,
     f: u8,
-    b: u8,
+    pub b: u8,
     pub c: u8,
-    d: u8,
-    e: u8,
-    h: u8,
-    l: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
     pub pc: u16,
     pub sp: u16,
 }
@@ -41,6 +23,12 @@ pub enum Flags {
     C = 0b00010000,
 }
 
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Registers {
     pub fn new() -> Self {
         Self {
@@ -96,15 +84,21 @@ impl Registers {
         hl
     }
 
+    /// The low nibble of F doesn't exist and always reads 0.
     pub fn set_af(&mut self, value: u16) {
         self.a = ((value & 0xff00) >> 8) as u8;
-        self.f = value as u8;
+        self.f = value as u8 & 0xf0;
     }
 
     pub fn get_af(&self) -> u16 {
         ((self.a as u16) << 8) | (self.f as u16)
     }
 
+    /// F is only written through `set_af` and the flag helpers.
+    pub fn get_f(&self) -> u8 {
+        self.f
+    }
+
     pub fn set_flags(&mut self, flag: Flags, value: bool) {
         match value {
             true => self.f |= flag as u8,
@@ -132,84 +126,75 @@ impl Registers {
         self.set_flags(Flags::H, overflow);
     }
 
-    pub fn get_word(&self, r8_code: u8, mmu: &mut MMU) -> Result<u8, io::Error> {
-        match r8_code {
-            A_REGISTER_CODE => Ok(self.a),
-            B_REGISTER_CODE => Ok(self.b),
-            C_REGISTER_CODE => Ok(self.c),
-            D_REGISTER_CODE => Ok(self.d),
-            E_REGISTER_CODE => Ok(self.e),
-            H_REGISTER_CODE => Ok(self.h),
-            L_REGISTER_CODE => Ok(self.l),
-            HL_MEM_REGISTER_CODE => Ok(mmu.get_word(self.get_hl() as usize)?),
-            _ => Err(error::invalid_r8_code()),
+    pub fn get_word(&self, r8: R8, bus: &mut impl Bus) -> Result<u8, EmuError> {
+        match r8 {
+            R8::A => Ok(self.a),
+            R8::B => Ok(self.b),
+            R8::C => Ok(self.c),
+            R8::D => Ok(self.d),
+            R8::E => Ok(self.e),
+            R8::H => Ok(self.h),
+            R8::L => Ok(self.l),
+            R8::HLMem => bus.get_word(self.get_hl() as usize),
         }
     }
 
-    pub fn get_dword(&self, r16_code: u8) -> Result<u16, io::Error> {
-        match r16_code {
-            BC_REGISTER_CODE => Ok(self.get_bc()),
-            HL_REGISTER_CODE => Ok(self.get_hl()),
-            DE_REGISTER_CODE => Ok(self.get_de()),
-            SP_REGISTER_CODE => Ok(self.sp),
-            _ => Err(error::invalid_r16_code()),
+    pub fn get_dword(&self, r16: R16) -> u16 {
+        match r16 {
+            R16::BC => self.get_bc(),
+            R16::DE => self.get_de(),
+            R16::HL => self.get_hl(),
+            R16::SP => self.sp,
         }
     }
 
-    pub fn get_dword_stk(&self, r16_code: u8) -> Result<u16, io::Error> {
-        match r16_code {
-            BC_REGISTER_CODE => Ok(self.get_bc()),
-            HL_REGISTER_CODE => Ok(self.get_hl()),
-            DE_REGISTER_CODE => Ok(self.get_de()),
-            AF_REGISTER_CODE => Ok(self.get_af()),
-            _ => Err(error::invalid_r16_code()),
+    pub fn get_dword_stk(&self, r16: R16Stk) -> u16 {
+        match r16 {
+            R16Stk::BC => self.get_bc(),
+            R16Stk::DE => self.get_de(),
+            R16Stk::HL => self.get_hl(),
+            R16Stk::AF => self.get_af(),
         }
     }
 
-    pub fn get_dword_mem(&mut self, r16_code: u8) -> Result<u16, io::Error> {
-        match r16_code {
-            BC_MEM_REGISTER_CODE => Ok(self.get_bc()),
-            DE_MEM_REGISTER_CODE => Ok(self.get_de()),
-            HLI_MEM_REGISTER_CODE => Ok(self.get_hli()),
-            HLD_MEM_REGISTER_CODE => Ok(self.get_hld()),
-            _ => Err(error::invalid_r16_code()),
+    pub fn get_dword_mem(&mut self, r16: R16Mem) -> u16 {
+        match r16 {
+            R16Mem::BC => self.get_bc(),
+            R16Mem::DE => self.get_de(),
+            R16Mem::HLI => self.get_hli(),
+            R16Mem::HLD => self.get_hld(),
         }
     }
 
-    pub fn set_word(&mut self, r8_code: u8, value: u8, mmu: &mut MMU) -> Result<(), io::Error> {
-        match r8_code {
-            A_REGISTER_CODE => self.a = value,
-            B_REGISTER_CODE => self.b = value,
-            C_REGISTER_CODE => self.c = value,
-            D_REGISTER_CODE => self.d = value,
-            E_REGISTER_CODE => self.e = value,
-            H_REGISTER_CODE => self.h = value,
-            L_REGISTER_CODE => self.l = value,
-            HL_MEM_REGISTER_CODE => mmu.set_word(self.get_hl() as usize, value)?,
-            _ => return Err(error::invalid_r8_code()),
+    pub fn set_word(&mut self, r8: R8, value: u8, bus: &mut impl Bus) -> Result<(), EmuError> {
+        match r8 {
+            R8::A => self.a = value,
+            R8::B => self.b = value,
+            R8::C => self.c = value,
+            R8::D => self.d = value,
+            R8::E => self.e = value,
+            R8::H => self.h = value,
+            R8::L => self.l = value,
+            R8::HLMem => bus.set_word(self.get_hl() as usize, value)?,
         }
         Ok(())
     }
 
-    pub fn set_dword(&mut self, binary_register: u8, value: u16) -> Result<(), io::Error> {
-        match binary_register {
-            BC_REGISTER_CODE => self.set_bc(value),
-            HL_REGISTER_CODE => self.set_hl(value),
-            DE_REGISTER_CODE => self.set_de(value),
-            SP_REGISTER_CODE => self.sp = value,
-            _ => return Err(error::invalid_r16_code()),
+    pub fn set_dword(&mut self, r16: R16, value: u16) {
+        match r16 {
+            R16::BC => self.set_bc(value),
+            R16::DE => self.set_de(value),
+            R16::HL => self.set_hl(value),
+            R16::SP => self.sp = value,
         }
-        Ok(())
     }
 
-    pub fn set_dword_stk(&mut self, binary_register: u8, value: u16) -> Result<(), io::Error> {
-        match binary_register {
-            BC_REGISTER_CODE => self.set_bc(value),
-            HL_REGISTER_CODE => self.set_hl(value),
-            DE_REGISTER_CODE => self.set_de(value),
-            AF_REGISTER_CODE => self.set_af(value),
-            _ => return Err(error::invalid_r16_code()),
+    pub fn set_dword_stk(&mut self, r16: R16Stk, value: u16) {
+        match r16 {
+            R16Stk::BC => self.set_bc(value),
+            R16Stk::DE => self.set_de(value),
+            R16Stk::HL => self.set_hl(value),
+            R16Stk::AF => self.set_af(value),
         }
-        Ok(())
     }
 }
@@ -0,0 +1,45 @@
+use std::io::Write;
+
+use crate::error::EmuError;
+
+use crate::bus::Bus;
+use crate::cpu::registers::Registers;
+
+/// Bytes from PC onward shown in the `PCMEM` column.
+const PCMEM_LENGTH: u16 = 4;
+
+/// Writes one gameboy-doctor line describing the state before an
+/// instruction executes:
+///
+/// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`
+pub fn write_line(
+    out: &mut impl Write,
+    registers: &Registers,
+    bus: &mut impl Bus,
+) -> Result<(), EmuError> {
+    let mut pcmem = [0xFF; PCMEM_LENGTH as usize];
+    for (offset, byte) in (0..PCMEM_LENGTH).zip(pcmem.iter_mut()) {
+        // Unmapped bytes read as open bus rather than aborting the trace.
+        if let Some(value) = bus.peek(registers.pc.wrapping_add(offset) as usize) {
+            *byte = value;
+        }
+    }
+    Ok(writeln!(
+        out,
+        "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+        registers.a,
+        registers.get_f(),
+        registers.b,
+        registers.c,
+        registers.d,
+        registers.e,
+        registers.h,
+        registers.l,
+        registers.sp,
+        registers.pc,
+        pcmem[0],
+        pcmem[1],
+        pcmem[2],
+        pcmem[3],
+    )?)
+}
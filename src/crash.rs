@@ -0,0 +1,84 @@
+use std::io::Write;
+
+use crate::bus::Bus;
+use crate::cpu::registers::Flags;
+use crate::cpu::CPU;
+use crate::error::EmuError;
+use crate::mmu::MMU;
+
+/// Bytes shown per line of a memory dump.
+const DUMP_LINE_WIDTH: u16 = 0x10;
+/// Lines shown before and after the line holding the dumped address.
+const DUMP_CONTEXT_LINES: u16 = 2;
+
+/// Writes a human readable description of the machine state after `error`
+/// stopped the emulation: registers, flags, IME, the mapped ROM bank, the
+/// instruction history kept by the CPU and memory around PC and SP.
+pub fn write_report(
+    out: &mut impl Write,
+    error: &EmuError,
+    cpu: &CPU,
+    mmu: &mut MMU,
+) -> Result<(), EmuError> {
+    let registers = &cpu.registers;
+    let flag = |flag: Flags| registers.get_flag(flag) as u8;
+
+    writeln!(out, "=== gbmu crash report ===")?;
+    writeln!(out, "Error: {}", error)?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "AF:{:04X} BC:{:04X} DE:{:04X} HL:{:04X} SP:{:04X} PC:{:04X}",
+        registers.get_af(),
+        registers.get_bc(),
+        registers.get_de(),
+        registers.get_hl(),
+        registers.sp,
+        registers.pc
+    )?;
+    writeln!(
+        out,
+        "Flags: Z:{} N:{} H:{} C:{}  IME:{}  State:{:?}",
+        flag(Flags::Z),
+        flag(Flags::N),
+        flag(Flags::H),
+        flag(Flags::C),
+        cpu.ime() as u8,
+        cpu.state()
+    )?;
+    writeln!(out, "ROM bank: {:02X}", mmu.rom_bank())?;
+
+    writeln!(out)?;
+    writeln!(out, "Last instructions:")?;
+    for disassembly in cpu.history() {
+        writeln!(out, "  {:04X}  {}", disassembly.address, disassembly)?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Memory around PC:")?;
+    dump_memory(out, mmu, registers.pc)?;
+    writeln!(out)?;
+    writeln!(out, "Memory around SP:")?;
+    dump_memory(out, mmu, registers.sp)
+}
+
+/// Hex dump of the lines surrounding `address`, unmapped bytes shown as `--`.
+fn dump_memory(out: &mut impl Write, bus: &mut impl Bus, address: u16) -> Result<(), EmuError> {
+    let line = address / DUMP_LINE_WIDTH;
+    let first = line.saturating_sub(DUMP_CONTEXT_LINES);
+    let last = line
+        .saturating_add(DUMP_CONTEXT_LINES)
+        .min(u16::MAX / DUMP_LINE_WIDTH);
+    for line in first..=last {
+        let start = line * DUMP_LINE_WIDTH;
+        write!(out, "  {:04X}:", start)?;
+        for offset in 0..DUMP_LINE_WIDTH {
+            match bus.peek((start + offset) as usize) {
+                Some(value) => write!(out, " {:02X}", value)?,
+                None => write!(out, " --")?,
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
@@ -1,44 +1,125 @@
-use std::io;
+use std::{error, fmt, io};
 
-pub fn invalid_argument() -> io::Error {
-    io::Error::new(io::ErrorKind::InvalidInput, "Invalid argument")
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
 }
 
-pub fn unsupported_instruction() -> io::Error {
-    io::Error::new(io::ErrorKind::Unsupported, "Instruction is unsupported")
+/// Errors surfaced by the emulator core.
+///
+/// Errors raised below the CPU (bus, cartridge) don't know which instruction
+/// caused them: their `pc` is `None` until the CPU fills it in through
+/// `with_pc`.
+#[derive(Debug)]
+pub enum EmuError {
+    UnsupportedOpcode {
+        opcode: u8,
+        pc: u16,
+    },
+    UnmappedAccess {
+        address: usize,
+        kind: AccessKind,
+        pc: Option<u16>,
+    },
+    InvalidRom {
+        reason: String,
+    },
+    UnsupportedCartridge {
+        cartridge_type: u8,
+    },
+    BootRomMissing {
+        path: String,
+    },
+    InvalidArgument {
+        argument: String,
+    },
+    Io(io::Error),
 }
 
-pub fn invalid_r8_code() -> io::Error {
-    io::Error::new(io::ErrorKind::InvalidInput, "Invalid r8 code")
+impl EmuError {
+    /// Attaches the address of the faulting instruction.
+    pub fn with_pc(self, at: u16) -> Self {
+        match self {
+            EmuError::UnsupportedOpcode { opcode, .. } => {
+                EmuError::UnsupportedOpcode { opcode, pc: at }
+            }
+            EmuError::UnmappedAccess { address, kind, .. } => EmuError::UnmappedAccess {
+                address,
+                kind,
+                pc: Some(at),
+            },
+            error => error,
+        }
+    }
 }
 
-pub fn invalid_r16_code() -> io::Error {
-    io::Error::new(io::ErrorKind::InvalidInput, "Invalid r16 code")
+impl fmt::Display for AccessKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccessKind::Read => write!(f, "read from"),
+            AccessKind::Write => write!(f, "write to"),
+        }
+    }
 }
 
-pub fn invalid_instruction_type() -> io::Error {
-    io::Error::new(io::ErrorKind::InvalidInput, "Invalid instruction type")
+impl fmt::Display for EmuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmuError::UnsupportedOpcode { opcode, pc } => {
+                write!(f, "Unsupported opcode {:#04x} at {:#06x}", opcode, pc)
+            }
+            EmuError::UnmappedAccess { address, kind, pc } => {
+                write!(f, "Unmapped {} {:#06x}", kind, address)?;
+                match pc {
+                    Some(pc) => write!(f, " at {:#06x}", pc),
+                    None => Ok(()),
+                }
+            }
+            EmuError::InvalidRom { reason } => write!(f, "Invalid rom: {}", reason),
+            EmuError::UnsupportedCartridge { cartridge_type } => {
+                write!(f, "Unsupported cartridge type {:#04x}", cartridge_type)
+            }
+            EmuError::BootRomMissing { path } => write!(f, "Boot rom missing: {}", path),
+            EmuError::InvalidArgument { argument } => {
+                write!(f, "Invalid argument: {}", argument)
+            }
+            EmuError::Io(error) => error.fmt(f),
+        }
+    }
 }
 
-pub fn invalid_condition_code() -> io::Error {
-    io::Error::new(io::ErrorKind::InvalidInput, "Invalid condition code")
+impl error::Error for EmuError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            EmuError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
 }
 
-pub fn invalid_flag() -> io::Error {
-    io::Error::new(io::ErrorKind::InvalidInput, "Invalid flag")
+impl From<io::Error> for EmuError {
+    fn from(error: io::Error) -> Self {
+        EmuError::Io(error)
+    }
 }
 
-pub fn invalid_opcode() -> io::Error {
-    io::Error::new(io::ErrorKind::InvalidInput, "Invalid opcode")
+pub fn invalid_argument(argument: &str) -> EmuError {
+    EmuError::InvalidArgument {
+        argument: argument.to_string(),
+    }
 }
 
-pub fn invalid_address(address: usize) -> io::Error {
-    io::Error::new(
-        io::ErrorKind::InvalidInput,
-        format!("Invalid address {:#x}", address),
-    )
+pub fn invalid_rom(reason: &str) -> EmuError {
+    EmuError::InvalidRom {
+        reason: reason.to_string(),
+    }
 }
 
-pub fn invalid_rom_size() -> io::Error {
-    io::Error::new(io::ErrorKind::InvalidInput, "Invalid rom size")
+pub fn unmapped_access(address: usize, kind: AccessKind) -> EmuError {
+    EmuError::UnmappedAccess {
+        address,
+        kind,
+        pc: None,
+    }
 }
@@ -0,0 +1,102 @@
+pub const INTERRUPT_MASK: u8 = 0b00011111;
+pub const INTERRUPT_SERVICE_CYCLES: u8 = 5;
+
+const UNUSED_FLAG_BITS: u8 = !INTERRUPT_MASK;
+
+/// Interrupt sources, in priority order. The discriminant is the bit index
+/// in both IF and IE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    VBlank = 0,
+    Stat = 1,
+    Timer = 2,
+    Serial = 3,
+    Joypad = 4,
+}
+
+impl Interrupt {
+    const PRIORITY: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::Stat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    pub fn mask(self) -> u8 {
+        1 << self as u8
+    }
+
+    pub fn vector(self) -> u16 {
+        0x40 + 8 * self as u16
+    }
+}
+
+/// Returns the highest priority interrupt set in an IF & IE style `pending`
+/// mask.
+pub fn highest_priority(pending: u8) -> Option<Interrupt> {
+    Interrupt::PRIORITY
+        .into_iter()
+        .find(|interrupt| pending & interrupt.mask() != 0)
+}
+
+#[derive(Debug, Default)]
+pub struct InterruptController {
+    flag: u8,
+    enable: u8,
+}
+
+impl InterruptController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.flag |= interrupt.mask();
+    }
+
+    pub fn acknowledge(&mut self, interrupt: Interrupt) {
+        self.flag &= !interrupt.mask();
+    }
+
+    pub fn is_requested(&self, interrupt: Interrupt) -> bool {
+        self.flag & interrupt.mask() != 0
+    }
+
+    /// Returns the highest priority interrupt that is both requested and
+    /// enabled, regardless of IME.
+    pub fn pending(&self) -> Option<Interrupt> {
+        highest_priority(self.flag & self.enable)
+    }
+
+    pub fn get_flag(&self) -> u8 {
+        self.flag | UNUSED_FLAG_BITS
+    }
+
+    pub fn set_flag(&mut self, value: u8) {
+        self.flag = value & INTERRUPT_MASK;
+    }
+
+    pub fn get_enable(&self) -> u8 {
+        self.enable
+    }
+
+    pub fn set_enable(&mut self, value: u8) {
+        self.enable = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_upper_bits_read_as_1() {
+        let mut interrupts = InterruptController::new();
+        assert_eq!(interrupts.get_flag(), 0xE0);
+        interrupts.set_flag(0xFF);
+        assert_eq!(interrupts.get_flag(), 0xFF);
+        interrupts.acknowledge(Interrupt::VBlank);
+        assert_eq!(interrupts.get_flag(), 0xFE);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::interrupts::{Interrupt, InterruptController};
+
+pub const P1_REGISTER: usize = 0xFF00;
+
+const SELECT_MASK: u8 = 0b00110000;
+const SELECT_DIRECTIONS: u8 = 0b00010000;
+const SELECT_BUTTONS: u8 = 0b00100000;
+const UNUSED_BITS: u8 = 0b11000000;
+const RELEASED: u8 = 0x0F;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    fn is_direction(self) -> bool {
+        matches!(
+            self,
+            Button::Right | Button::Left | Button::Up | Button::Down
+        )
+    }
+
+    /// Bit of the button in the low nibble of P1, active low.
+    fn mask(self) -> u8 {
+        match self {
+            Button::Right | Button::A => 0b0001,
+            Button::Left | Button::B => 0b0010,
+            Button::Up | Button::Select => 0b0100,
+            Button::Down | Button::Start => 0b1000,
+        }
+    }
+}
+
+/// P1: the program selects the direction and/or button lines through bits
+/// 4-5 and reads the pressed keys of the selected lines as 0 in bits 0-3.
+#[derive(Debug)]
+pub struct Joypad {
+    select: u8,
+    directions: u8,
+    buttons: u8,
+}
+
+impl Default for Joypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Joypad {
+            select: 0,
+            directions: 0,
+            buttons: 0,
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        let mut keys = RELEASED;
+        if self.select & SELECT_DIRECTIONS == 0 {
+            keys &= !self.directions;
+        }
+        if self.select & SELECT_BUTTONS == 0 {
+            keys &= !self.buttons;
+        }
+        UNUSED_BITS | self.select | keys
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.select = value & SELECT_MASK;
+    }
+
+    /// Updates a key, requesting the joypad interrupt when a selected line
+    /// goes low.
+    pub fn set_button(
+        &mut self,
+        button: Button,
+        pressed: bool,
+        interrupts: &mut InterruptController,
+    ) {
+        let before = self.read();
+        let keys = match button.is_direction() {
+            true => &mut self.directions,
+            false => &mut self.buttons,
+        };
+        match pressed {
+            true => *keys |= button.mask(),
+            false => *keys &= !button.mask(),
+        }
+        if before & !self.read() & RELEASED != 0 {
+            interrupts.request(Interrupt::Joypad);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn p1_unused_bits_read_as_1() {
+        let mut joypad = Joypad::new();
+        joypad.write(0x00);
+        assert_eq!(joypad.read(), 0xCF);
+        joypad.write(0xFF);
+        assert_eq!(joypad.read(), 0xFF);
+    }
+}
@@ -1,5 +1,15 @@
+pub mod apu;
+pub mod boot;
+pub mod bus;
 pub mod cartridge;
+pub mod clock;
 pub mod cpu;
+pub mod crash;
 pub mod error;
+pub mod interrupts;
+pub mod joypad;
 pub mod mmu;
+pub mod model;
 pub mod ppu;
+pub mod serial;
+pub mod timer;
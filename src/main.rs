@@ -1,30 +1,248 @@
+use gbmu::boot;
 use gbmu::cartridge;
+use gbmu::clock::{Clock, FixedClock, SystemClock};
+use gbmu::cpu::disasm;
 use gbmu::cpu::CPU;
-use gbmu::error;
+use gbmu::crash;
+use gbmu::error::{self, EmuError};
+use gbmu::mmu::boot_rom::BootRom;
 use gbmu::mmu::MMU;
-use gbmu::ppu;
+use gbmu::model::Model;
+use signal_hook::consts::SIGINT;
 use std::env;
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-use gbmu::cartridge::Cartridge;
-use std::error::Error;
-use std::io;
-use std::io::Read;
+const ROM_BANK_SIZE: usize = 0x4000;
+/// Overrides where boot ROMs are looked up when `--boot-rom-dir` isn't given.
+const BOOT_ROM_DIRECTORY_VARIABLE: &str = "GBMU_BOOT_ROM_DIR";
+/// Boot ROMs shipped next to the executable otherwise.
+const BOOT_ROM_DIRECTORY: &str = "power_up";
+/// The repository's boot ROMs, for `cargo run` from a checkout.
+const SOURCE_BOOT_ROM_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/power_up");
+/// Instructions kept for the crash report.
+const CRASH_HISTORY_SIZE: usize = 32;
+/// M-cycles between checks for Ctrl-C and for a save to write, about a
+/// frame.
+const SAVE_CHECK_CYCLES: u32 = 17556;
+/// Checks between saves written whether or not the game asked, about a
+/// minute, for games that never disable RAM.
+const PERIODIC_SAVE_CHECKS: u32 = 3600;
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() -> Result<(), EmuError> {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        println!("Usage: ./{} <ROM>", args[0]);
-        return Err(Box::new(error::invalid_argument()));
+    match args.get(1).map(String::as_str) {
+        Some("disasm") => disasm_command(&args),
+        Some(_) => run(&args),
+        None => {
+            usage(&args[0]);
+            Err(error::invalid_argument("missing ROM"))
+        }
     }
+}
+
+fn usage(program: &str) {
+    println!("Usage: {} <ROM> [OPTIONS]", program);
+    println!("       {} disasm <ROM> [--bank N] [--from ADDR]", program);
+    println!("Options:");
+    println!("  --model MODEL         dmg0, dmg, mgb, sgb, sgb2, cgb0, cgb, agb0, agb or stadium2");
+    println!("                        (default: from the cartridge header)");
+    println!("  --boot-rom-dir DIR    directory holding the <model>.bin boot ROMs");
+    println!(
+        "                        (default: ${}, else {} next to the executable,",
+        BOOT_ROM_DIRECTORY_VARIABLE, BOOT_ROM_DIRECTORY
+    );
+    println!("                        else the one of the source tree)");
+    println!("  --skip-boot           start at 0x0100 in the post-boot state");
+    println!("  --no-ppu-locks        let the CPU access VRAM and OAM in any PPU mode");
+    println!("  --fixed-time SECONDS  date cartridge clocks and saves at this Unix time");
+    println!("  --trace FILE|-        log every instruction in gameboy-doctor format");
+    println!("  --crash-report FILE   also write the crash report to FILE");
+}
+
+fn run(args: &[String]) -> Result<(), EmuError> {
     let rom_path = &args[1];
-    let mut cartridge = cartridge::Cartridge::load_rom(rom_path).unwrap();
-    let mut mmu = MMU::new(&mut cartridge.mbc, false);
+    let mut trace = None;
+    let mut crash_report = None;
+    let mut skip_boot = false;
+    let mut ppu_locks = true;
+    let mut fixed_time = None;
+    let mut model = None;
+    let mut boot_rom_directory = None;
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        let mut value = || {
+            options
+                .next()
+                .ok_or_else(|| error::invalid_argument(option))
+        };
+        match option.as_str() {
+            "--trace" => trace = Some(value()?),
+            "--crash-report" => crash_report = Some(value()?),
+            "--model" => model = Some(value()?.parse::<Model>()?),
+            "--skip-boot" => skip_boot = true,
+            "--no-ppu-locks" => ppu_locks = false,
+            "--fixed-time" => fixed_time = Some(parse_number(value()?)?),
+            "--boot-rom-dir" => boot_rom_directory = Some(PathBuf::from(value()?)),
+            _ => return Err(error::invalid_argument(option)),
+        }
+    }
+
+    let clock: Box<dyn Clock> = match fixed_time {
+        Some(now) => Box::new(FixedClock::new(now as u64)),
+        None => Box::new(SystemClock),
+    };
+    let mut cartridge = cartridge::Cartridge::load_rom_with_clock(rom_path, clock)?;
+    let save_file = cartridge.save_file();
+    let model = model.unwrap_or_else(|| Model::detect(&cartridge.header));
+    let mut mmu = MMU::new(cartridge.mbc.as_mut(), model);
+    mmu.set_ppu_locks(ppu_locks);
+    if !skip_boot {
+        let directory = match boot_rom_directory {
+            Some(directory) => directory,
+            None => default_boot_rom_directory()?,
+        };
+        let path = directory.join(model.boot_rom());
+        mmu.map_boot_rom(BootRom::load(&path)?);
+    }
     let mut cpu = CPU::new();
+    if skip_boot {
+        boot::skip_boot_rom(model, &cartridge.header, &mut cpu, &mut mmu)?;
+    }
+    cpu.set_history_size(CRASH_HISTORY_SIZE);
+    match trace.map(String::as_str) {
+        Some("-") => cpu.set_trace(Box::new(LineWriter::new(io::stdout()))),
+        Some(path) => cpu.set_trace(Box::new(LineWriter::new(File::create(path)?))),
+        None => {}
+    }
+
+    // Ctrl-C stops at the next check so the game is saved; a second one
+    // exits right away.
+    let interrupted = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register_conditional_shutdown(SIGINT, 1, Arc::clone(&interrupted))?;
+    signal_hook::flag::register(SIGINT, Arc::clone(&interrupted))?;
+
+    let mut cycles = 0;
+    let mut checks = 0;
+    let error = loop {
+        match cpu.run(&mut mmu) {
+            Ok(step) => cycles += step as u32,
+            Err(error) => break Some(error),
+        }
+        if cycles < SAVE_CHECK_CYCLES {
+            continue;
+        }
+        cycles = 0;
+        if interrupted.load(Ordering::Relaxed) {
+            break None;
+        }
+        checks += 1;
+        if let Some(save_file) = &save_file {
+            if mmu.take_flush_request() || checks >= PERIODIC_SAVE_CHECKS {
+                checks = 0;
+                // A failed save is retried at the next one rather than
+                // stopping the game.
+                if let Err(error) = save_file.write(mmu.mbc()) {
+                    eprintln!("Failed to save: {}", error);
+                }
+            }
+        }
+    };
+
+    // Save first, so the crash report failing can't lose the game.
+    let saved = match &save_file {
+        Some(save_file) => save_file.write(mmu.mbc()),
+        None => Ok(()),
+    };
+    let Some(error) = error else {
+        return saved;
+    };
+    crash::write_report(&mut io::stderr(), &error, &cpu, &mut mmu)?;
+    if let Some(path) = crash_report {
+        crash::write_report(&mut File::create(path)?, &error, &cpu, &mut mmu)?;
+    }
+    saved?;
+    Err(error)
+}
 
-    loop {
-        cpu.run(&mut mmu);
-        unsafe {
-            ppu::run(&mut mmu);
+fn default_boot_rom_directory() -> Result<PathBuf, EmuError> {
+    if let Some(directory) = env::var_os(BOOT_ROM_DIRECTORY_VARIABLE) {
+        return Ok(PathBuf::from(directory));
+    }
+    let executable = env::current_exe()?;
+    let directory = executable
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(BOOT_ROM_DIRECTORY);
+    match directory.is_dir() {
+        true => Ok(directory),
+        false => Ok(PathBuf::from(SOURCE_BOOT_ROM_DIRECTORY)),
+    }
+}
+
+/// Disassembles one ROM bank as mapped in the CPU address space: bank 0 at
+/// 0x0000-0x3FFF, any other bank at 0x4000-0x7FFF.
+fn disasm_command(args: &[String]) -> Result<(), EmuError> {
+    let Some(rom_path) = args.get(2) else {
+        usage(&args[0]);
+        return Err(error::invalid_argument("missing ROM"));
+    };
+    let mut bank = 0;
+    let mut from = None;
+    let mut options = args[3..].iter();
+    while let Some(option) = options.next() {
+        let value = options
+            .next()
+            .ok_or_else(|| error::invalid_argument(option))?;
+        match option.as_str() {
+            "--bank" => bank = parse_number(value)?,
+            "--from" => from = Some(parse_number(value)?),
+            _ => return Err(error::invalid_argument(option)),
         }
     }
+
+    let rom = std::fs::read(rom_path)?;
+    let bank_start = match bank.checked_mul(ROM_BANK_SIZE) {
+        Some(start) if start < rom.len() => start,
+        _ => return Err(error::invalid_rom("Bank is out of the rom")),
+    };
+    let bank_data = &rom[bank_start..rom.len().min(bank_start + ROM_BANK_SIZE)];
+    let base = if bank == 0 { 0 } else { ROM_BANK_SIZE };
+    let from = from.unwrap_or(base);
+    if from < base || from >= base + bank_data.len() {
+        return Err(error::invalid_argument(&format!("--from {:#06x}", from)));
+    }
+
+    let mut out = io::stdout().lock();
+    let mut offset = from - base;
+    while let Some(disassembly) = disasm::disassemble(&bank_data[offset..], (base + offset) as u16)
+    {
+        let length = disassembly.length as usize;
+        let bytes: Vec<String> = bank_data[offset..offset + length]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        writeln!(
+            out,
+            "{:02X}:{:04X}  {:<9} {}",
+            bank,
+            disassembly.address,
+            bytes.join(" "),
+            disassembly
+        )?;
+        offset += length;
+    }
+    Ok(())
+}
+
+fn parse_number(value: &str) -> Result<usize, EmuError> {
+    let parsed = if let Some(hex) = value.strip_prefix("0x").or(value.strip_prefix('$')) {
+        usize::from_str_radix(hex, 16)
+    } else {
+        value.parse()
+    };
+    parsed.map_err(|_| error::invalid_argument(value))
 }
@@ -1,15 +1,27 @@
-use std::io;
-
-use crate::cartridge::mbc0::MBC0;
-use crate::error;
-use gpio::GPIO;
+use crate::apu::APU;
+use crate::bus::{Bus, IE_REGISTER};
+use crate::cartridge::MemoryBankController;
+use crate::error::{self, AccessKind, EmuError};
+use crate::interrupts::{Interrupt, InterruptController};
+use crate::joypad::{Button, Joypad};
+use crate::model::Model;
+use crate::ppu::{Mode, PPU};
+use crate::serial::Serial;
+use crate::timer::Timer;
+use boot_rom::BootRom;
+use dma::OamDma;
+use hdma::Hdma;
 use hram::HRAM;
+use io::{IO_END, IO_START};
 use oam::OAM;
 use vram::VRAM;
 use wram::WRAM;
 
-pub mod gpio;
+pub mod boot_rom;
+pub mod dma;
+pub mod hdma;
 pub mod hram;
+pub mod io;
 pub mod oam;
 pub mod vram;
 pub mod wram;
@@ -20,88 +32,412 @@ const ROM_START: usize = 0x0000;
 const ROM_END: usize = 0x7FFF;
 #[allow(unused)]
 const ROM_DWORD_END: usize = ROM_END - 1;
-#[allow(unused)]
 const VRAM_START: usize = 0x8000;
-#[allow(unused)]
 const VRAM_END: usize = 0x9FFF;
-#[allow(unused)]
 const EXRAM_START: usize = 0xA000;
-#[allow(unused)]
 const EXRAM_END: usize = 0xBFFF;
 const WRAM_START: usize = 0xC000;
-#[allow(unused)]
 const WRAM_END: usize = 0xDFFF;
+const ECHO_START: usize = 0xE000;
+const ECHO_END: usize = 0xFDFF;
 #[allow(unused)]
 const WRAM_DWORD_END: usize = WRAM_END - 1;
-#[allow(unused)]
 const OAM_START: usize = 0xFE00;
-#[allow(unused)]
 const OAM_END: usize = 0xFE9F;
-const GPIO_START: usize = 0xFF00;
-const GPIO_END: usize = 0xFF7F;
+const PROHIBITED_START: usize = 0xFEA0;
+const PROHIBITED_END: usize = 0xFEFF;
 const HRAM_START: usize = 0xFF80;
 const HRAM_END: usize = 0xFFFE;
-const IE_REGISTER: usize = 0xFFFF;
 
 pub struct MMU<'a> {
-    mbc: &'a mut MBC0,
+    mbc: &'a mut dyn MemoryBankController,
     wram: WRAM,
     hram: HRAM,
-    gpio: GPIO,
     vram: VRAM,
     oam: OAM,
-    ie: u8,
+    interrupts: InterruptController,
+    ppu: PPU,
+    timer: Timer,
+    joypad: Joypad,
+    serial: Serial,
+    apu: APU,
+    oam_dma: OamDma,
+    hdma: Hdma,
+    /// Whether VRAM and OAM are locked while the PPU uses them.
+    ppu_locks: bool,
+    /// M-cycles the CPU is halted for by VRAM DMA.
+    stall_cycles: u16,
+    /// Whether the CPU is in HALT, which pauses HBlank DMA.
+    cpu_halted: bool,
+    model: Model,
+    /// CGB hardware running a cartridge without CGB support is in DMG
+    /// compatibility mode, chosen by the boot ROM through KEY0.
+    cgb_mode: bool,
+    boot_rom: Option<BootRom>,
+    double_speed: bool,
+    speed_switch_armed: bool,
 }
 
 impl<'a> MMU<'a> {
-    pub fn new(mbc: &'a mut MBC0, cbg_mode: bool) -> Self {
-        let wram = WRAM::new(cbg_mode);
+    pub fn new(mbc: &'a mut dyn MemoryBankController, model: Model) -> Self {
+        let wram = WRAM::new(model.is_cgb());
         let hram = HRAM::new();
-        let gpio = GPIO::new();
-        let vram = VRAM::new(cbg_mode);
+        let vram = VRAM::new(model.is_cgb());
         let oam = OAM::new();
         MMU {
             mbc,
             wram,
             hram,
-            gpio,
             vram,
             oam,
-            ie: u8::default(),
+            interrupts: InterruptController::new(),
+            ppu: PPU::new(model.is_cgb()),
+            timer: Timer::new(),
+            joypad: Joypad::new(),
+            serial: Serial::new(model.is_cgb()),
+            apu: APU::new(),
+            oam_dma: OamDma::new(),
+            hdma: Hdma::new(),
+            ppu_locks: true,
+            stall_cycles: 0,
+            cpu_halted: false,
+            model,
+            cgb_mode: model.is_cgb(),
+            boot_rom: None,
+            double_speed: false,
+            speed_switch_armed: false,
         }
     }
 
-    pub fn get_word(&mut self, address: usize) -> Result<u8, io::Error> {
-        Ok(*(self.fetch_word_address(address)?))
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupts.request(interrupt);
     }
 
-    pub fn get_dword(&mut self, address: usize) -> Result<u16, io::Error> {
-        let mut dword = self.get_word(address)? as u16;
-        dword |= (self.get_word(address + 1)? as u16) << 8;
-        Ok(dword)
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        self.joypad
+            .set_button(button, pressed, &mut self.interrupts);
     }
 
-    pub fn set_word(&mut self, address: usize, value: u8) -> Result<(), io::Error> {
-        *self.fetch_word_address(address)? = value;
-        Ok(())
+    pub fn ppu(&self) -> &PPU {
+        &self.ppu
     }
 
-    pub fn set_dword(&mut self, address: usize, value: u16) -> Result<(), io::Error> {
-        self.set_word(address, (value & 0x00FF) as u8)?;
-        self.set_word(address + 1, (value >> 8) as u8)?;
-        Ok(())
+    pub fn serial(&self) -> &Serial {
+        &self.serial
     }
 
-    fn fetch_word_address(&mut self, address: usize) -> Result<&mut u8, io::Error> {
+    /// The cartridge controller, e.g. to save its state.
+    pub fn mbc(&self) -> &dyn MemoryBankController {
+        self.mbc
+    }
+
+    pub fn take_flush_request(&mut self) -> bool {
+        self.mbc.take_flush_request()
+    }
+
+    pub fn serial_mut(&mut self) -> &mut Serial {
+        &mut self.serial
+    }
+
+    /// Maps `boot_rom` over the cartridge until 0xFF50 is written.
+    pub fn map_boot_rom(&mut self, boot_rom: BootRom) {
+        self.boot_rom = Some(boot_rom);
+    }
+
+    pub fn boot_rom_mapped(&self) -> bool {
+        self.boot_rom.is_some()
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    pub fn rom_bank(&self) -> usize {
+        self.mbc.rom_bank()
+    }
+
+    pub fn double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    /// Enables the VRAM and OAM locks. Disabling them lets the CPU access
+    /// both in any PPU mode, hiding timing bugs real hardware would expose.
+    pub fn set_ppu_locks(&mut self, enabled: bool) {
+        self.ppu_locks = enabled;
+    }
+
+    /// Whether the PPU currently owns `address`: VRAM during mode 3 and
+    /// OAM during modes 2 and 3. Reads return 0xFF and writes are dropped.
+    fn locked_by_ppu(&self, address: usize) -> bool {
+        let mode = self.ppu.mode();
+        self.ppu_locks
+            && match address {
+                VRAM_START..=VRAM_END => mode == Mode::Drawing,
+                OAM_START..=OAM_END => matches!(mode, Mode::OamScan | Mode::Drawing),
+                _ => false,
+            }
+    }
+
+    /// Reads from the unusable area after OAM: the high nibble of the
+    /// address repeated on CGB and AGB. Monochrome models read 0, or 0xFF
+    /// while OAM is blocked by the PPU or OAM DMA.
+    fn get_prohibited(&self, address: usize) -> u8 {
+        match self.model.is_cgb() {
+            true => {
+                let nibble = (address & 0xF0) as u8;
+                nibble | nibble >> 4
+            }
+            false if self.locked_by_ppu(OAM_START) || self.oam_dma.active() => 0xFF,
+            false => 0x00,
+        }
+    }
+
+    fn clock(&mut self, cycles: u8) {
+        self.tick_oam_dma(cycles);
+        self.timer.tick(cycles, &mut self.interrupts);
+        self.serial.tick(cycles, &mut self.interrupts);
+        self.mbc.tick(cycles, self.double_speed);
+        for _ in 0..cycles {
+            let mode = self.ppu.mode();
+            self.ppu.tick(1, self.double_speed, &mut self.interrupts);
+            if mode != Mode::HBlank && self.ppu.mode() == Mode::HBlank {
+                self.step_hblank_dma();
+            }
+        }
+    }
+
+    /// Reads `address` ignoring OAM DMA bus conflicts.
+    fn get_unconflicted_word(&mut self, address: usize) -> Result<u8, EmuError> {
+        if let Some(byte) = self.boot_rom.as_ref().and_then(|rom| rom.get(address)) {
+            return Ok(byte);
+        }
+        match address {
+            IE_REGISTER => Ok(self.interrupts.get_enable()),
+            IO_START..=IO_END => Ok(self.read_io(address)),
+            ROM_START..=ROM_END => Ok(self.mbc.rom_read(address)),
+            EXRAM_START..=EXRAM_END => Ok(self.mbc.ram_read(address - EXRAM_START)),
+            PROHIBITED_START..=PROHIBITED_END => Ok(self.get_prohibited(address)),
+            _ => Ok(*(self.fetch_word_address(address, AccessKind::Read)?)),
+        }
+    }
+
+    fn fetch_word_address(
+        &mut self,
+        address: usize,
+        kind: AccessKind,
+    ) -> Result<&mut u8, EmuError> {
         match address {
-            ROM_START..=ROM_END => Ok(&mut self.mbc[address]),
             WRAM_START..=WRAM_END => Ok(&mut self.wram[address - WRAM_START]),
+            ECHO_START..=ECHO_END => Ok(&mut self.wram[address - ECHO_START]),
             HRAM_START..=HRAM_END => Ok(&mut self.hram[address - HRAM_START]),
-            GPIO_START..=GPIO_END => Ok(&mut self.gpio[address - GPIO_START]),
             VRAM_START..=VRAM_END => Ok(&mut self.vram[address - VRAM_START]),
             OAM_START..=OAM_END => Ok(&mut self.oam[address - OAM_START]),
-            IE_REGISTER => Ok(&mut self.ie),
-            _ => Err(error::invalid_address(address)),
+            _ => Err(error::unmapped_access(address, kind)),
+        }
+    }
+}
+
+impl Bus for MMU<'_> {
+    fn get_word(&mut self, address: usize) -> Result<u8, EmuError> {
+        if self.locked_by_ppu(address) {
+            return Ok(0xFF);
+        }
+        match self.oam_dma_read_conflict(address) {
+            Some(byte) => Ok(byte),
+            None => self.get_unconflicted_word(address),
+        }
+    }
+
+    /// Sees through PPU locks and OAM DMA bus conflicts.
+    fn peek(&mut self, address: usize) -> Option<u8> {
+        self.get_unconflicted_word(address).ok()
+    }
+
+    fn set_word(&mut self, address: usize, value: u8) -> Result<(), EmuError> {
+        if self.locked_by_ppu(address) || self.oam_dma_write_conflict(address) {
+            return Ok(());
+        }
+        match address {
+            IE_REGISTER => self.interrupts.set_enable(value),
+            IO_START..=IO_END => self.write_io(address, value),
+            ROM_START..=ROM_END => self.mbc.rom_write(address, value),
+            EXRAM_START..=EXRAM_END => self.mbc.ram_write(address - EXRAM_START, value),
+            PROHIBITED_START..=PROHIBITED_END => {}
+            _ => *self.fetch_word_address(address, AccessKind::Write)? = value,
         }
+        Ok(())
+    }
+
+    fn set_cpu_halted(&mut self, halted: bool) {
+        self.cpu_halted = halted;
+    }
+
+    /// Clocks the components driven by the system clock, then lets the
+    /// time the CPU spends halted by VRAM DMA pass.
+    fn tick(&mut self, cycles: u8) {
+        self.clock(cycles);
+        while self.stall_cycles > 0 {
+            let cycles = self.stall_cycles.min(u8::MAX as u16);
+            self.stall_cycles -= cycles;
+            self.clock(cycles as u8);
+        }
+    }
+
+    fn pending_interrupt(&mut self) -> Result<Option<Interrupt>, EmuError> {
+        Ok(self.interrupts.pending())
+    }
+
+    fn interrupt_requested(&mut self, interrupt: Interrupt) -> Result<bool, EmuError> {
+        Ok(self.interrupts.is_requested(interrupt))
+    }
+
+    fn acknowledge_interrupt(&mut self, interrupt: Interrupt) -> Result<(), EmuError> {
+        self.interrupts.acknowledge(interrupt);
+        Ok(())
+    }
+
+    /// Performs the CGB speed switch prepared through KEY1, returning whether
+    /// STOP should switch speed instead of stopping the system.
+    fn switch_speed(&mut self) -> bool {
+        if !self.cgb_mode || !self.speed_switch_armed {
+            return false;
+        }
+        self.speed_switch_armed = false;
+        self.double_speed = !self.double_speed;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::mbc0::MBC0;
+    use crate::mmu::io::{DMA_REGISTER, SVBK_REGISTER};
+    use crate::ppu::LCDC_REGISTER;
+
+    const LCD_ON: u8 = 0x91;
+
+    fn mbc() -> MBC0 {
+        MBC0::new(vec![0; 0x8000], 0x2000)
+    }
+
+    /// Ticks until the PPU is in `mode`.
+    fn tick_until(mmu: &mut MMU, mode: Mode) {
+        while mmu.ppu().mode() != mode {
+            mmu.tick(1);
+        }
+    }
+
+    #[test]
+    fn echo_mirrors_wram() {
+        let mut mbc = mbc();
+        let mut mmu = MMU::new(&mut mbc, Model::DMG);
+        mmu.set_word(0xC123, 0x12).unwrap();
+        assert_eq!(mmu.get_word(0xE123).unwrap(), 0x12);
+        mmu.set_word(0xFDFF, 0x34).unwrap();
+        assert_eq!(mmu.get_word(0xDDFF).unwrap(), 0x34);
+    }
+
+    #[test]
+    fn echo_follows_the_cgb_wram_bank() {
+        let mut mbc = mbc();
+        let mut mmu = MMU::new(&mut mbc, Model::CGB);
+        mmu.set_word(SVBK_REGISTER, 3).unwrap();
+        mmu.set_word(0xD000, 0x33).unwrap();
+        assert_eq!(mmu.get_word(0xF000).unwrap(), 0x33);
+        mmu.set_word(SVBK_REGISTER, 2).unwrap();
+        assert_eq!(mmu.get_word(0xF000).unwrap(), 0x00);
+        mmu.set_word(0xF000, 0x22).unwrap();
+        assert_eq!(mmu.get_word(0xD000).unwrap(), 0x22);
+        mmu.set_word(SVBK_REGISTER, 3).unwrap();
+        assert_eq!(mmu.get_word(0xF000).unwrap(), 0x33);
+    }
+
+    #[test]
+    fn external_ram_goes_through_the_mbc() {
+        let mut mbc = mbc();
+        let mut mmu = MMU::new(&mut mbc, Model::DMG);
+        mmu.set_word(0xA123, 0x5A).unwrap();
+        assert_eq!(mmu.get_word(0xA123).unwrap(), 0x5A);
+        drop(mmu);
+        assert_eq!(mbc.ram_read(0x0123), 0x5A);
+    }
+
+    #[test]
+    fn prohibited_area_ignores_writes() {
+        for model in [Model::DMG, Model::CGB] {
+            let mut mbc = mbc();
+            let mut mmu = MMU::new(&mut mbc, model);
+            let before = mmu.get_word(0xFEA0).unwrap();
+            mmu.set_word(0xFEA0, 0x5A).unwrap();
+            mmu.set_word(0xFEFF, 0x5A).unwrap();
+            assert_eq!(mmu.get_word(0xFEA0).unwrap(), before);
+            assert_ne!(mmu.get_word(0xFEFF).unwrap(), 0x5A);
+        }
+    }
+
+    #[test]
+    fn prohibited_area_reads_ff_while_oam_is_blocked() {
+        let mut mbc = mbc();
+        let mut mmu = MMU::new(&mut mbc, Model::DMG);
+        assert_eq!(mmu.get_word(0xFEA0).unwrap(), 0x00);
+        mmu.set_word(LCDC_REGISTER, LCD_ON).unwrap();
+        tick_until(&mut mmu, Mode::OamScan);
+        assert_eq!(mmu.get_word(0xFEA0).unwrap(), 0xFF);
+        tick_until(&mut mmu, Mode::Drawing);
+        assert_eq!(mmu.get_word(0xFEA0).unwrap(), 0xFF);
+        tick_until(&mut mmu, Mode::HBlank);
+        assert_eq!(mmu.get_word(0xFEA0).unwrap(), 0x00);
+
+        mmu.set_word(DMA_REGISTER, 0xC0).unwrap();
+        mmu.tick(1);
+        assert_eq!(mmu.get_word(0xFEA0).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn cgb_prohibited_area_repeats_the_address_nibble() {
+        let mut mbc = mbc();
+        let mut mmu = MMU::new(&mut mbc, Model::CGB);
+        assert_eq!(mmu.get_word(0xFEA0).unwrap(), 0xAA);
+        assert_eq!(mmu.get_word(0xFED7).unwrap(), 0xDD);
+    }
+
+    /// Writes `value` to VRAM and OAM and reads both back.
+    fn access_video(mmu: &mut MMU, value: u8) -> (u8, u8) {
+        mmu.set_word(VRAM_START, value).unwrap();
+        mmu.set_word(OAM_START, value).unwrap();
+        (
+            mmu.get_word(VRAM_START).unwrap(),
+            mmu.get_word(OAM_START).unwrap(),
+        )
+    }
+
+    #[test]
+    fn ppu_locks_follow_the_mode() {
+        let mut mbc = mbc();
+        let mut mmu = MMU::new(&mut mbc, Model::DMG);
+        assert_eq!(access_video(&mut mmu, 0x11), (0x11, 0x11));
+        mmu.set_word(LCDC_REGISTER, LCD_ON).unwrap();
+
+        tick_until(&mut mmu, Mode::OamScan);
+        assert_eq!(access_video(&mut mmu, 0x22), (0x22, 0xFF));
+        tick_until(&mut mmu, Mode::Drawing);
+        assert_eq!(access_video(&mut mmu, 0x33), (0xFF, 0xFF));
+        tick_until(&mut mmu, Mode::HBlank);
+        assert_eq!(mmu.get_word(VRAM_START).unwrap(), 0x22);
+        assert_eq!(mmu.get_word(OAM_START).unwrap(), 0x11);
+        assert_eq!(access_video(&mut mmu, 0x44), (0x44, 0x44));
+    }
+
+    #[test]
+    fn ppu_locks_can_be_disabled() {
+        let mut mbc = mbc();
+        let mut mmu = MMU::new(&mut mbc, Model::DMG);
+        mmu.set_ppu_locks(false);
+        mmu.set_word(LCDC_REGISTER, LCD_ON).unwrap();
+        tick_until(&mut mmu, Mode::OamScan);
+        assert_eq!(access_video(&mut mmu, 0x22), (0x22, 0x22));
+        tick_until(&mut mmu, Mode::Drawing);
+        assert_eq!(access_video(&mut mmu, 0x33), (0x33, 0x33));
     }
 }
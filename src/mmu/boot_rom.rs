@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::EmuError;
+
+const DMG_BOOT_ROM_END: usize = 0x100;
+/// CGB boot ROMs resume after the cartridge header, up to 0x8FF.
+const CGB_BOOT_ROM_RESUME: usize = 0x200;
+
+/// Boot ROM mapped over the start of the cartridge until the program
+/// writes to 0xFF50.
+pub struct BootRom {
+    data: Box<[u8]>,
+}
+
+impl BootRom {
+    pub fn new(data: Vec<u8>) -> Self {
+        BootRom {
+            data: data.into_boxed_slice(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, EmuError> {
+        let data = fs::read(path).map_err(|_| EmuError::BootRomMissing {
+            path: path.display().to_string(),
+        })?;
+        Ok(Self::new(data))
+    }
+
+    /// The boot ROM byte at `address`, if the boot ROM covers it.
+    pub fn get(&self, address: usize) -> Option<u8> {
+        match address {
+            0..DMG_BOOT_ROM_END | CGB_BOOT_ROM_RESUME.. => self.data.get(address).copied(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::Bus;
+    use crate::cartridge::mbc0::MBC0;
+    use crate::mmu::io::BOOT_REGISTER;
+    use crate::mmu::MMU;
+    use crate::model::Model;
+
+    const BOOT_BYTE: u8 = 0xB0;
+    const CARTRIDGE_BYTE: u8 = 0xCA;
+    const CGB_BOOT_ROM_SIZE: usize = 0x900;
+
+    fn mbc() -> MBC0 {
+        MBC0::new(vec![CARTRIDGE_BYTE; 0x8000], 0)
+    }
+
+    #[test]
+    fn writing_ff50_unmaps_the_boot_rom() {
+        let mut mbc = mbc();
+        let mut mmu = MMU::new(&mut mbc, Model::DMG);
+        mmu.map_boot_rom(BootRom::new(vec![BOOT_BYTE; DMG_BOOT_ROM_END]));
+        assert_eq!(mmu.get_word(0x0000).unwrap(), BOOT_BYTE);
+        assert_eq!(mmu.get_word(0x00FF).unwrap(), BOOT_BYTE);
+        assert_eq!(mmu.get_word(0x0100).unwrap(), CARTRIDGE_BYTE);
+
+        mmu.set_word(BOOT_REGISTER, 0x01).unwrap();
+        assert!(!mmu.boot_rom_mapped());
+        assert_eq!(mmu.get_word(0x0000).unwrap(), CARTRIDGE_BYTE);
+    }
+
+    #[test]
+    fn cgb_boot_rom_leaves_the_header_to_the_cartridge() {
+        let mut mbc = mbc();
+        let mut mmu = MMU::new(&mut mbc, Model::CGB);
+        mmu.map_boot_rom(BootRom::new(vec![BOOT_BYTE; CGB_BOOT_ROM_SIZE]));
+        for address in DMG_BOOT_ROM_END..CGB_BOOT_ROM_RESUME {
+            assert_eq!(mmu.get_word(address).unwrap(), CARTRIDGE_BYTE);
+        }
+        assert_eq!(mmu.get_word(CGB_BOOT_ROM_RESUME).unwrap(), BOOT_BYTE);
+        assert_eq!(mmu.get_word(CGB_BOOT_ROM_SIZE - 1).unwrap(), BOOT_BYTE);
+        assert_eq!(mmu.get_word(CGB_BOOT_ROM_SIZE).unwrap(), CARTRIDGE_BYTE);
+    }
+}
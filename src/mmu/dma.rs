@@ -0,0 +1,243 @@
+use crate::mmu::oam::OAM_SIZE;
+use crate::mmu::{ECHO_END, ECHO_START, MMU, OAM_END, OAM_START, VRAM_END, VRAM_START, WRAM_START};
+
+/// M-cycles between the write to 0xFF46 and the first byte copied.
+const STARTUP_CYCLES: u8 = 1;
+
+/// Sources from 0xE000 up read from WRAM, like the echo area.
+const ECHO_OFFSET: usize = 0x2000;
+
+/// The buses DMA can read from. A CPU access on the bus the transfer is
+/// using conflicts with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DmaBus {
+    External,
+    Video,
+    /// CGB and AGB have WRAM on a bus of its own, apart from the cartridge.
+    WorkRam,
+}
+
+impl DmaBus {
+    fn of(address: usize, cgb: bool) -> Self {
+        match address {
+            VRAM_START..=VRAM_END => DmaBus::Video,
+            WRAM_START..=ECHO_END if cgb => DmaBus::WorkRam,
+            _ => DmaBus::External,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Transfer {
+    start: usize,
+    index: usize,
+}
+
+#[derive(Debug)]
+struct Pending {
+    source: u8,
+    delay: u8,
+}
+
+/// OAM DMA: after a write to 0xFF46 copies one byte per M-cycle from
+/// XX00-XX9F into OAM, 160 M-cycles in total. A new write starts a new
+/// transfer once its startup cycle is over; until then the running one
+/// carries on, so OAM stays locked through a restart.
+#[derive(Debug, Default)]
+pub struct OamDma {
+    register: u8,
+    transfer: Option<Transfer>,
+    pending: Option<Pending>,
+    last_byte: u8,
+}
+
+impl OamDma {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self) -> u8 {
+        self.register
+    }
+
+    /// Sets the register without starting a transfer.
+    pub fn set_register(&mut self, value: u8) {
+        self.register = value;
+    }
+
+    pub fn start(&mut self, source: u8) {
+        self.register = source;
+        self.pending = Some(Pending {
+            source,
+            delay: STARTUP_CYCLES,
+        });
+    }
+
+    pub fn active(&self) -> bool {
+        self.transfer.is_some()
+    }
+
+    /// Advances the startup of a pending transfer, replacing the running
+    /// one when it is over.
+    fn step_pending(&mut self) {
+        if let Some(pending) = self.pending.as_mut() {
+            pending.delay -= 1;
+            if pending.delay == 0 {
+                let start = (pending.source as usize) << 8;
+                self.transfer = Some(Transfer { start, index: 0 });
+                self.pending = None;
+            }
+        }
+    }
+
+    /// Address of the next byte to copy and its offset in OAM.
+    fn next(&self) -> Option<(usize, usize)> {
+        let transfer = self.transfer.as_ref()?;
+        let address = match transfer.start + transfer.index {
+            address if address >= ECHO_START => address - ECHO_OFFSET,
+            address => address,
+        };
+        Some((address, transfer.index))
+    }
+
+    fn advance(&mut self, byte: u8) {
+        self.last_byte = byte;
+        if let Some(transfer) = self.transfer.as_mut() {
+            transfer.index += 1;
+            if transfer.index == OAM_SIZE {
+                self.transfer = None;
+            }
+        }
+    }
+
+    fn bus(&self, cgb: bool) -> Option<DmaBus> {
+        self.transfer
+            .as_ref()
+            .map(|transfer| DmaBus::of(transfer.start, cgb))
+    }
+}
+
+impl MMU<'_> {
+    /// Runs OAM DMA for `cycles` M-cycles.
+    pub(super) fn tick_oam_dma(&mut self, cycles: u8) {
+        for _ in 0..cycles {
+            if let Some((address, offset)) = self.oam_dma.next() {
+                let byte = self.get_unconflicted_word(address).unwrap_or(0xFF);
+                self.oam[offset] = byte;
+                self.oam_dma.advance(byte);
+            }
+            self.oam_dma.step_pending();
+        }
+    }
+
+    /// What a CPU read of `address` sees while a transfer runs, if it is
+    /// affected: OAM reads 0xFF and the bus in use returns the byte being
+    /// copied. The IO registers and HRAM stay accessible.
+    pub(super) fn oam_dma_read_conflict(&self, address: usize) -> Option<u8> {
+        let cgb = self.model.is_cgb();
+        let bus = self.oam_dma.bus(cgb)?;
+        match address {
+            OAM_START..=OAM_END => Some(0xFF),
+            _ if address >= OAM_START => None,
+            _ if DmaBus::of(address, cgb) == bus => Some(self.oam_dma.last_byte),
+            _ => None,
+        }
+    }
+
+    /// Whether a CPU write to `address` is dropped by a running transfer.
+    pub(super) fn oam_dma_write_conflict(&self, address: usize) -> bool {
+        let cgb = self.model.is_cgb();
+        match self.oam_dma.bus(cgb) {
+            Some(bus) => match address {
+                OAM_START..=OAM_END => true,
+                _ if address >= OAM_START => false,
+                _ => DmaBus::of(address, cgb) == bus,
+            },
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::Bus;
+    use crate::cartridge::mbc0::MBC0;
+    use crate::mmu::io::DMA_REGISTER;
+    use crate::model::Model;
+
+    const ROM_SOURCE: u8 = 0x01;
+    const WRAM_SOURCE: u8 = 0xC1;
+    const WRAM_BYTE: usize = 0xC000;
+    const HRAM_BYTE: usize = 0xFF80;
+
+    /// ROM bytes are the low byte of their address plus one, so they differ
+    /// from zeroed WRAM.
+    fn mbc() -> MBC0 {
+        let rom = (0..0x8000).map(|address| (address as u8).wrapping_add(1));
+        MBC0::new(rom.collect(), 0)
+    }
+
+    fn mmu(mbc: &mut MBC0, model: Model) -> MMU<'_> {
+        let mut mmu = MMU::new(mbc, model);
+        mmu.set_ppu_locks(false);
+        mmu
+    }
+
+    #[test]
+    fn starts_one_cycle_after_the_write() {
+        let mut mbc = mbc();
+        let mut mmu = mmu(&mut mbc, Model::DMG);
+        mmu.set_word(DMA_REGISTER, ROM_SOURCE).unwrap();
+        assert_eq!(mmu.get_word(OAM_START).unwrap(), 0x00);
+        mmu.tick(1);
+        assert_eq!(mmu.get_word(OAM_START).unwrap(), 0xFF);
+        mmu.tick(OAM_SIZE as u8);
+        assert_eq!(mmu.get_word(OAM_START).unwrap(), 0x01);
+        assert_eq!(mmu.get_word(OAM_END).unwrap(), 0xA0);
+    }
+
+    #[test]
+    fn restart_keeps_oam_locked_and_copies_the_new_source() {
+        let mut mbc = mbc();
+        let mut mmu = mmu(&mut mbc, Model::DMG);
+        mmu.set_word(0xC100, 0x42).unwrap();
+        mmu.set_word(DMA_REGISTER, ROM_SOURCE).unwrap();
+        mmu.tick(80);
+        mmu.set_word(DMA_REGISTER, WRAM_SOURCE).unwrap();
+        assert_eq!(mmu.get_word(OAM_START).unwrap(), 0xFF);
+        mmu.tick(1);
+        assert_eq!(mmu.get_word(OAM_START).unwrap(), 0xFF);
+        mmu.tick(OAM_SIZE as u8);
+        assert_eq!(mmu.get_word(OAM_START).unwrap(), 0x42);
+        assert_eq!(mmu.get_word(OAM_START + 1).unwrap(), 0x00);
+    }
+
+    #[test]
+    fn dmg_wram_shares_the_bus_with_the_cartridge() {
+        let mut mbc = mbc();
+        let mut mmu = mmu(&mut mbc, Model::DMG);
+        mmu.set_word(DMA_REGISTER, ROM_SOURCE).unwrap();
+        mmu.tick(2);
+        assert_eq!(mmu.get_word(WRAM_BYTE).unwrap(), 0x01);
+        mmu.set_word(WRAM_BYTE, 0x55).unwrap();
+        mmu.set_word(HRAM_BYTE, 0x66).unwrap();
+        assert_eq!(mmu.get_word(HRAM_BYTE).unwrap(), 0x66);
+        assert_eq!(mmu.get_word(VRAM_START).unwrap(), 0x00);
+        mmu.tick(OAM_SIZE as u8);
+        assert_eq!(mmu.get_word(WRAM_BYTE).unwrap(), 0x00);
+    }
+
+    #[test]
+    fn cgb_wram_is_a_separate_bus() {
+        let mut mbc = mbc();
+        let mut mmu = mmu(&mut mbc, Model::CGB);
+        mmu.set_word(WRAM_BYTE, 0x55).unwrap();
+        mmu.set_word(DMA_REGISTER, ROM_SOURCE).unwrap();
+        mmu.tick(2);
+        assert_eq!(mmu.get_word(WRAM_BYTE).unwrap(), 0x55);
+        mmu.set_word(WRAM_BYTE, 0x66).unwrap();
+        assert_eq!(mmu.get_word(WRAM_BYTE).unwrap(), 0x66);
+        assert_eq!(mmu.get_word(0x4000).unwrap(), 0x01);
+    }
+}
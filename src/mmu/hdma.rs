@@ -0,0 +1,274 @@
+use crate::mmu::{MMU, VRAM_END, VRAM_START};
+use crate::ppu::Mode;
+
+pub const HDMA1_REGISTER: usize = 0xFF51;
+pub const HDMA2_REGISTER: usize = 0xFF52;
+pub const HDMA3_REGISTER: usize = 0xFF53;
+pub const HDMA4_REGISTER: usize = 0xFF54;
+pub const HDMA5_REGISTER: usize = 0xFF55;
+
+const BLOCK_SIZE: u16 = 0x10;
+const BLOCK_ALIGNMENT: u8 = 0xF0;
+const DESTINATION_HIGH_MASK: u8 = 0x1F;
+const DESTINATION_MASK: u16 = 0x1FF0;
+const HDMA5_HBLANK: u8 = 0b10000000;
+const HDMA5_LENGTH: u8 = 0b01111111;
+
+/// M-cycles the CPU is halted for each block, in normal and double speed.
+const BLOCK_CYCLES: u16 = 8;
+const DOUBLE_SPEED_BLOCK_CYCLES: u16 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    General,
+    HBlank,
+    Cancelled,
+}
+
+/// CGB VRAM DMA: copies blocks of 16 bytes from `source` to VRAM, either
+/// all at once (general-purpose DMA) or one block per HBlank.
+#[derive(Debug)]
+pub struct Hdma {
+    source: u16,
+    destination: u16,
+    /// Blocks left to copy minus one, as read from HDMA5.
+    length: u8,
+    state: State,
+}
+
+impl Default for Hdma {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hdma {
+    pub fn new() -> Self {
+        Hdma {
+            source: 0,
+            destination: 0,
+            length: HDMA5_LENGTH,
+            state: State::Idle,
+        }
+    }
+
+    /// HDMA1-HDMA4 are write-only. HDMA5 reads the remaining length with
+    /// bit 7 clear while an HBlank transfer runs, set once it is cancelled,
+    /// and 0xFF when no transfer is left.
+    pub fn read(&self, address: usize) -> u8 {
+        match (address, self.state) {
+            (HDMA5_REGISTER, State::HBlank) => self.length,
+            (HDMA5_REGISTER, State::Cancelled) => HDMA5_HBLANK | self.length,
+            _ => 0xFF,
+        }
+    }
+
+    /// Writes the address registers. The low nibbles are ignored and the
+    /// destination always lies in VRAM.
+    fn write_address(&mut self, address: usize, value: u8) {
+        match address {
+            HDMA1_REGISTER => self.source = (self.source & 0x00FF) | (value as u16) << 8,
+            HDMA2_REGISTER => {
+                self.source = (self.source & 0xFF00) | (value & BLOCK_ALIGNMENT) as u16
+            }
+            HDMA3_REGISTER => {
+                let high = (value & DESTINATION_HIGH_MASK) as u16;
+                self.destination = (self.destination & 0x00FF) | high << 8;
+            }
+            HDMA4_REGISTER => {
+                let low = (value & BLOCK_ALIGNMENT) as u16;
+                self.destination = (self.destination & 0xFF00) | low;
+            }
+            _ => {}
+        }
+    }
+
+    fn hblank_active(&self) -> bool {
+        self.state == State::HBlank
+    }
+
+    /// Source and VRAM offset of the next block, advancing past it.
+    fn next_block(&mut self) -> Option<(u16, u16)> {
+        if !matches!(self.state, State::General | State::HBlank) {
+            return None;
+        }
+        let block = (self.source, self.destination & DESTINATION_MASK);
+        self.source = self.source.wrapping_add(BLOCK_SIZE);
+        self.destination = (self.destination + BLOCK_SIZE) & DESTINATION_MASK;
+        self.length = self.length.wrapping_sub(1) & HDMA5_LENGTH;
+        if self.length == HDMA5_LENGTH {
+            self.state = State::Idle;
+        }
+        Some(block)
+    }
+}
+
+impl MMU<'_> {
+    pub(super) fn write_hdma(&mut self, address: usize, value: u8) {
+        if address != HDMA5_REGISTER {
+            self.hdma.write_address(address, value);
+            return;
+        }
+        if self.hdma.hblank_active() && value & HDMA5_HBLANK == 0 {
+            self.hdma.state = State::Cancelled;
+            return;
+        }
+        self.hdma.length = value & HDMA5_LENGTH;
+        match value & HDMA5_HBLANK {
+            0 => {
+                self.hdma.state = State::General;
+                while self.copy_hdma_block() {}
+            }
+            _ => {
+                self.hdma.state = State::HBlank;
+                // Starting in mode 0, e.g. with the LCD off, copies a block
+                // right away.
+                if self.ppu.mode() == Mode::HBlank {
+                    self.copy_hdma_block();
+                }
+            }
+        }
+    }
+
+    /// Copies one block of an HBlank transfer as the PPU enters mode 0,
+    /// unless the CPU is halted: the transfer then waits for the next
+    /// HBlank after it wakes up.
+    pub(super) fn step_hblank_dma(&mut self) {
+        if self.hdma.hblank_active() && !self.cpu_halted {
+            self.copy_hdma_block();
+        }
+    }
+
+    /// Copies the next block through the MMU and halts the CPU for the
+    /// time it takes. Returns whether a block was copied.
+    fn copy_hdma_block(&mut self) -> bool {
+        let Some((source, destination)) = self.hdma.next_block() else {
+            return false;
+        };
+        for offset in 0..BLOCK_SIZE {
+            let address = source.wrapping_add(offset) as usize;
+            // VRAM can't be a source; the bus reads open.
+            let byte = match address {
+                VRAM_START..=VRAM_END => 0xFF,
+                _ => self.get_unconflicted_word(address).unwrap_or(0xFF),
+            };
+            self.vram[(destination + offset) as usize] = byte;
+        }
+        self.stall_cycles += match self.double_speed {
+            true => DOUBLE_SPEED_BLOCK_CYCLES,
+            false => BLOCK_CYCLES,
+        };
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::Bus;
+    use crate::cartridge::mbc0::MBC0;
+    use crate::model::Model;
+    use crate::ppu::LCDC_REGISTER;
+
+    const SOURCE: usize = 0xC000;
+    const LCD_ON: u8 = 0x91;
+
+    fn mbc() -> MBC0 {
+        MBC0::new(vec![0; 0x8000], 0)
+    }
+
+    /// A CGB MMU with numbered bytes at `SOURCE` and the transfer set up to
+    /// copy them to the start of VRAM.
+    fn mmu(mbc: &mut MBC0) -> MMU<'_> {
+        let mut mmu = MMU::new(mbc, Model::CGB);
+        mmu.set_ppu_locks(false);
+        for offset in 0..0x40 {
+            mmu.set_word(SOURCE + offset, offset as u8 + 1).unwrap();
+        }
+        mmu.set_word(HDMA1_REGISTER, (SOURCE >> 8) as u8).unwrap();
+        mmu.set_word(HDMA2_REGISTER, SOURCE as u8).unwrap();
+        mmu.set_word(HDMA3_REGISTER, 0x80).unwrap();
+        mmu.set_word(HDMA4_REGISTER, 0x00).unwrap();
+        mmu
+    }
+
+    fn start_lcd(mmu: &mut MMU) {
+        mmu.set_word(LCDC_REGISTER, LCD_ON).unwrap();
+        mmu.tick(1);
+    }
+
+    /// Ticks until the PPU has entered HBlank once more.
+    fn next_hblank(mmu: &mut MMU) {
+        while mmu.ppu().mode() == Mode::HBlank {
+            mmu.tick(1);
+        }
+        while mmu.ppu().mode() != Mode::HBlank {
+            mmu.tick(1);
+        }
+    }
+
+    fn copied_blocks(mmu: &mut MMU) -> usize {
+        (0..4)
+            .take_while(|block| {
+                let address = VRAM_START + block * BLOCK_SIZE as usize;
+                mmu.get_word(address).unwrap() != 0
+            })
+            .count()
+    }
+
+    #[test]
+    fn general_dma_copies_length_plus_one_blocks() {
+        let mut mbc = mbc();
+        let mut mmu = mmu(&mut mbc);
+        mmu.set_word(HDMA5_REGISTER, 0x01).unwrap();
+        assert_eq!(copied_blocks(&mut mmu), 2);
+        assert_eq!(mmu.get_word(VRAM_START + 0x1F).unwrap(), 0x20);
+        assert_eq!(mmu.get_word(HDMA5_REGISTER).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn hblank_dma_copies_one_block_per_hblank() {
+        let mut mbc = mbc();
+        let mut mmu = mmu(&mut mbc);
+        start_lcd(&mut mmu);
+        mmu.set_word(HDMA5_REGISTER, HDMA5_HBLANK | 0x02).unwrap();
+        assert_eq!(copied_blocks(&mut mmu), 0);
+        assert_eq!(mmu.get_word(HDMA5_REGISTER).unwrap(), 0x02);
+        for (blocks, remaining) in [(1, 0x01), (2, 0x00), (3, 0xFF)] {
+            next_hblank(&mut mmu);
+            assert_eq!(copied_blocks(&mut mmu), blocks);
+            assert_eq!(mmu.get_word(HDMA5_REGISTER).unwrap(), remaining);
+        }
+        next_hblank(&mut mmu);
+        assert_eq!(copied_blocks(&mut mmu), 3);
+    }
+
+    #[test]
+    fn clearing_bit_7_cancels_an_hblank_dma() {
+        let mut mbc = mbc();
+        let mut mmu = mmu(&mut mbc);
+        start_lcd(&mut mmu);
+        mmu.set_word(HDMA5_REGISTER, HDMA5_HBLANK | 0x02).unwrap();
+        next_hblank(&mut mmu);
+        mmu.set_word(HDMA5_REGISTER, 0x00).unwrap();
+        assert_eq!(mmu.get_word(HDMA5_REGISTER).unwrap(), HDMA5_HBLANK | 0x01);
+        next_hblank(&mut mmu);
+        assert_eq!(copied_blocks(&mut mmu), 1);
+    }
+
+    #[test]
+    fn hblank_dma_pauses_while_the_cpu_is_halted() {
+        let mut mbc = mbc();
+        let mut mmu = mmu(&mut mbc);
+        start_lcd(&mut mmu);
+        mmu.set_word(HDMA5_REGISTER, HDMA5_HBLANK | 0x02).unwrap();
+        mmu.set_cpu_halted(true);
+        next_hblank(&mut mmu);
+        assert_eq!(copied_blocks(&mut mmu), 0);
+        assert_eq!(mmu.get_word(HDMA5_REGISTER).unwrap(), 0x02);
+        mmu.set_cpu_halted(false);
+        next_hblank(&mut mmu);
+        assert_eq!(copied_blocks(&mut mmu), 1);
+    }
+}
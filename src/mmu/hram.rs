@@ -7,6 +7,12 @@ pub struct HRAM {
     data: Box<[u8]>,
 }
 
+impl Default for HRAM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HRAM {
     pub fn new() -> Self {
         HRAM {
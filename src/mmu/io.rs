@@ -0,0 +1,128 @@
+use crate::apu::{APU_START, WAVE_RAM_END};
+use crate::bus::{Bus, IE_REGISTER, IF_REGISTER};
+use crate::error::EmuError;
+use crate::joypad::P1_REGISTER;
+use crate::mmu::hdma::{HDMA1_REGISTER, HDMA5_REGISTER};
+use crate::mmu::MMU;
+use crate::ppu::{
+    BCPS_REGISTER, BGP_REGISTER, LCDC_REGISTER, LYC_REGISTER, LY_REGISTER, OCPD_REGISTER,
+    WX_REGISTER,
+};
+use crate::serial::{SB_REGISTER, SC_REGISTER};
+use crate::timer::{DIV_REGISTER, TAC_REGISTER};
+
+pub const IO_START: usize = 0xFF00;
+pub const IO_END: usize = 0xFF7F;
+
+pub const DMA_REGISTER: usize = 0xFF46;
+pub const KEY0_REGISTER: usize = 0xFF4C;
+pub const KEY1_REGISTER: usize = 0xFF4D;
+pub const VBK_REGISTER: usize = 0xFF4F;
+pub const BOOT_REGISTER: usize = 0xFF50;
+pub const SVBK_REGISTER: usize = 0xFF70;
+
+/// Written to KEY0 by the CGB boot ROM for cartridges without CGB support.
+pub const KEY0_DMG_COMPATIBILITY: u8 = 0b00000100;
+const KEY1_ARMED: u8 = 0b00000001;
+const KEY1_UNUSED_BITS: u8 = 0b01111110;
+const VBK_UNUSED_BITS: u8 = 0b11111110;
+const SVBK_UNUSED_BITS: u8 = 0b11111000;
+
+impl MMU<'_> {
+    /// Reads an IO register from the component owning it. Unused bits and
+    /// unmapped registers read as 1.
+    pub(super) fn read_io(&self, address: usize) -> u8 {
+        match address {
+            P1_REGISTER => self.joypad.read(),
+            SB_REGISTER | SC_REGISTER => self.serial.read(address),
+            DIV_REGISTER..=TAC_REGISTER => self.timer.read(address),
+            IF_REGISTER => self.interrupts.get_flag(),
+            APU_START..=WAVE_RAM_END => self.apu.read(address),
+            LCDC_REGISTER..=LYC_REGISTER | BGP_REGISTER..=WX_REGISTER => self.ppu.read(address),
+            BCPS_REGISTER..=OCPD_REGISTER => self.ppu.read(address),
+            DMA_REGISTER => self.oam_dma.register(),
+            KEY1_REGISTER => self.get_key1(),
+            HDMA1_REGISTER..=HDMA5_REGISTER if self.cgb_mode => self.hdma.read(address),
+            VBK_REGISTER if self.cgb_mode => VBK_UNUSED_BITS | self.vram.bank(),
+            SVBK_REGISTER if self.cgb_mode => SVBK_UNUSED_BITS | self.wram.bank(),
+            _ => 0xFF,
+        }
+    }
+
+    pub(super) fn write_io(&mut self, address: usize, value: u8) {
+        match address {
+            P1_REGISTER => self.joypad.write(value),
+            SB_REGISTER | SC_REGISTER => self.serial.write(address, value),
+            DIV_REGISTER..=TAC_REGISTER => self.timer.write(address, value, &mut self.interrupts),
+            IF_REGISTER => self.interrupts.set_flag(value),
+            APU_START..=WAVE_RAM_END => self.apu.write(address, value),
+            LCDC_REGISTER..=LYC_REGISTER | BGP_REGISTER..=WX_REGISTER => {
+                self.ppu.write(address, value, &mut self.interrupts)
+            }
+            BCPS_REGISTER..=OCPD_REGISTER => self.ppu.write(address, value, &mut self.interrupts),
+            DMA_REGISTER => self.oam_dma.start(value),
+            KEY1_REGISTER => self.speed_switch_armed = self.cgb_mode && value & KEY1_ARMED != 0,
+            HDMA1_REGISTER..=HDMA5_REGISTER if self.cgb_mode => self.write_hdma(address, value),
+            VBK_REGISTER if self.cgb_mode => self.vram.set_bank(value),
+            SVBK_REGISTER if self.cgb_mode => self.wram.set_bank(value),
+            // Only the boot ROM picks the mode, before it unmaps itself.
+            KEY0_REGISTER if self.model.is_cgb() && self.boot_rom.is_some() => self.set_key0(value),
+            // Unmapping is permanent until the next reset.
+            BOOT_REGISTER if value != 0 => self.boot_rom = None,
+            _ => {}
+        }
+    }
+
+    /// Sets a register to the value it holds after the boot ROM, bypassing
+    /// write side effects: DIV keeps its value and LY moves the PPU.
+    pub fn set_io_state(&mut self, address: usize, value: u8) -> Result<(), EmuError> {
+        match address {
+            DIV_REGISTER => self.timer.set_div(value),
+            LY_REGISTER => self.ppu.set_line(value),
+            DMA_REGISTER => self.oam_dma.set_register(value),
+            KEY0_REGISTER if self.model.is_cgb() => self.set_key0(value),
+            IE_REGISTER => self.interrupts.set_enable(value),
+            _ => self.set_word(address, value)?,
+        }
+        Ok(())
+    }
+
+    /// KEY0 selects between CGB mode and DMG compatibility mode, where the
+    /// CGB registers are unmapped.
+    fn set_key0(&mut self, value: u8) {
+        self.cgb_mode = value & KEY0_DMG_COMPATIBILITY == 0;
+        self.ppu.set_cgb_mode(self.cgb_mode);
+        self.serial.set_cgb_mode(self.cgb_mode);
+    }
+
+    fn get_key1(&self) -> u8 {
+        match self.cgb_mode {
+            true => {
+                let double_speed = (self.double_speed as u8) << 7;
+                double_speed | KEY1_UNUSED_BITS | self.speed_switch_armed as u8
+            }
+            false => 0xFF,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::mbc0::MBC0;
+    use crate::model::Model;
+
+    #[test]
+    fn key1_unused_bits_read_as_1() {
+        let mut mbc = MBC0::new(vec![0; 0x8000], 0);
+        let mut mmu = MMU::new(&mut mbc, Model::DMG);
+        mmu.set_word(KEY1_REGISTER, KEY1_ARMED).unwrap();
+        assert_eq!(mmu.get_word(KEY1_REGISTER).unwrap(), 0xFF);
+
+        let mut mbc = MBC0::new(vec![0; 0x8000], 0);
+        let mut mmu = MMU::new(&mut mbc, Model::CGB);
+        assert_eq!(mmu.get_word(KEY1_REGISTER).unwrap(), 0x7E);
+        mmu.set_word(KEY1_REGISTER, KEY1_ARMED).unwrap();
+        assert_eq!(mmu.get_word(KEY1_REGISTER).unwrap(), 0x7F);
+    }
+}
@@ -3,6 +3,7 @@ use std::ops::{Index, IndexMut};
 const DMG_VRAM_SIZE: usize = 1 << 13; //  8 192
 const CBG_VRAM_SIZE: usize = 1 << 14; // 16 384
 const BANK_WIDTH: usize = 1 << 13;
+const BANK_MASK: u8 = 0b1;
 
 pub struct VRAM {
     data: Box<[u8]>,
@@ -18,6 +19,15 @@ impl VRAM {
         };
         VRAM { data, bank: 0 }
     }
+
+    pub fn bank(&self) -> u8 {
+        self.bank
+    }
+
+    /// Selects the bank mapped at 0x8000-0x9FFF.
+    pub fn set_bank(&mut self, bank: u8) {
+        self.bank = bank & BANK_MASK;
+    }
 }
 
 impl Index<usize> for VRAM {
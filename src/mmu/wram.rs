@@ -3,6 +3,7 @@ use std::ops::{Index, IndexMut};
 const DMG_WRAM_SIZE: usize = 1 << 13; //  8 192
 const CBG_WRAM_SIZE: usize = 1 << 15; // 32 768
 const BANK_WIDTH: usize = 0x1000;
+const BANK_MASK: u8 = 0b111;
 
 pub struct WRAM {
     data: Box<[u8]>,
@@ -17,7 +18,21 @@ impl WRAM {
             vec![0; DMG_WRAM_SIZE].into_boxed_slice()
         };
 
-        WRAM { data, bank: 1 }
+        WRAM { data, bank: 0 }
+    }
+
+    pub fn bank(&self) -> u8 {
+        self.bank
+    }
+
+    /// Selects the bank mapped at 0xD000-0xDFFF. Bank 0 is always mapped
+    /// at 0xC000, so selecting it maps bank 1 instead.
+    pub fn set_bank(&mut self, bank: u8) {
+        self.bank = bank & BANK_MASK;
+    }
+
+    fn switchable_offset(&self) -> usize {
+        (self.bank.max(1) - 1) as usize * BANK_WIDTH
     }
 }
 
@@ -28,7 +43,7 @@ impl Index<usize> for WRAM {
         if (index) < BANK_WIDTH {
             &self.data[index]
         } else {
-            &self.data[index + (self.bank - 1) as usize * BANK_WIDTH]
+            &self.data[index + self.switchable_offset()]
         }
     }
 }
@@ -38,7 +53,7 @@ impl IndexMut<usize> for WRAM {
         if (index) < BANK_WIDTH {
             &mut self.data[index]
         } else {
-            &mut self.data[index + (self.bank - 1) as usize * BANK_WIDTH]
+            &mut self.data[index + self.switchable_offset()]
         }
     }
 }
@@ -0,0 +1,95 @@
+use std::str::FromStr;
+
+use crate::cartridge::header::CartridgeHeader;
+use crate::error::{self, EmuError};
+
+const CGB_FLAG_SUPPORTED: u8 = 0x80;
+const SGB_FLAG_SUPPORTED: u8 = 0x03;
+/// SGB functions are only enabled with the new licensee code scheme.
+const OLD_LICENSEE_USE_NEW: u8 = 0x33;
+
+/// Game Boy hardware revisions with observable differences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    /// Original Game Boy, early boot ROM revision.
+    DMG0,
+    DMG,
+    /// Game Boy Pocket.
+    MGB,
+    SGB,
+    SGB2,
+    /// Game Boy Color, early boot ROM revision.
+    CGB0,
+    CGB,
+    /// Game Boy Advance running Game Boy software, early boot ROM revision.
+    AGB0,
+    /// Game Boy Advance running Game Boy software.
+    AGB,
+    /// The GB Tower of Pokémon Stadium 2: a Game Boy Color emulated by the
+    /// Nintendo 64, with its own boot ROM.
+    Stadium2,
+}
+
+impl Model {
+    /// Picks the most capable hardware the cartridge declares support for.
+    pub fn detect(header: &CartridgeHeader) -> Self {
+        if header.cgb_flag() & CGB_FLAG_SUPPORTED != 0 {
+            Model::CGB
+        } else if header.sgb_flag[0] == SGB_FLAG_SUPPORTED
+            && header.old_licensee_code[0] == OLD_LICENSEE_USE_NEW
+        {
+            Model::SGB
+        } else {
+            Model::DMG
+        }
+    }
+
+    /// Whether the model has the Game Boy Color hardware (banked VRAM/WRAM,
+    /// double speed, color palettes).
+    pub fn is_cgb(self) -> bool {
+        matches!(
+            self,
+            Model::CGB0 | Model::CGB | Model::AGB0 | Model::AGB | Model::Stadium2
+        )
+    }
+
+    pub fn is_sgb(self) -> bool {
+        matches!(self, Model::SGB | Model::SGB2)
+    }
+
+    /// File name of the model's boot ROM in the power_up directory.
+    pub fn boot_rom(self) -> &'static str {
+        match self {
+            Model::DMG0 => "dmg0.bin",
+            Model::DMG => "dmg.bin",
+            Model::MGB => "mgb.bin",
+            Model::SGB => "sgb.bin",
+            Model::SGB2 => "sgb2.bin",
+            Model::CGB0 => "cgb0.bin",
+            Model::CGB => "cgb.bin",
+            Model::AGB0 => "agb0.bin",
+            Model::AGB => "agb.bin",
+            Model::Stadium2 => "stadium2.bin",
+        }
+    }
+}
+
+impl FromStr for Model {
+    type Err = EmuError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "dmg0" => Ok(Model::DMG0),
+            "dmg" => Ok(Model::DMG),
+            "mgb" => Ok(Model::MGB),
+            "sgb" => Ok(Model::SGB),
+            "sgb2" => Ok(Model::SGB2),
+            "cgb0" => Ok(Model::CGB0),
+            "cgb" => Ok(Model::CGB),
+            "agb0" => Ok(Model::AGB0),
+            "agb" => Ok(Model::AGB),
+            "stadium2" => Ok(Model::Stadium2),
+            _ => Err(error::invalid_argument(name)),
+        }
+    }
+}
@@ -1,21 +1,305 @@
-use crate::mmu::MMU;
+use crate::interrupts::{Interrupt, InterruptController};
 
-use std::io;
+pub const LCDC_REGISTER: usize = 0xFF40;
+pub const STAT_REGISTER: usize = 0xFF41;
+pub const SCY_REGISTER: usize = 0xFF42;
+pub const SCX_REGISTER: usize = 0xFF43;
+pub const LY_REGISTER: usize = 0xFF44;
+pub const LYC_REGISTER: usize = 0xFF45;
+pub const BGP_REGISTER: usize = 0xFF47;
+pub const OBP0_REGISTER: usize = 0xFF48;
+pub const OBP1_REGISTER: usize = 0xFF49;
+pub const WY_REGISTER: usize = 0xFF4A;
+pub const WX_REGISTER: usize = 0xFF4B;
+pub const BCPS_REGISTER: usize = 0xFF68;
+pub const BCPD_REGISTER: usize = 0xFF69;
+pub const OCPS_REGISTER: usize = 0xFF6A;
+pub const OCPD_REGISTER: usize = 0xFF6B;
 
-const LY_REGISTER: u16 = 0xFF44;
+const DOTS_PER_M_CYCLE: u32 = 4;
+const DOTS_PER_LINE: u32 = 456;
+const OAM_SCAN_DOTS: u32 = 80;
+const DRAWING_DOTS: u32 = 172;
+const VBLANK_LINE: u8 = 144;
+const LINES_PER_FRAME: u8 = 154;
 
-pub unsafe fn run(mmu: &mut MMU) -> Result<(), io::Error> {
-    static mut cycle: u32 = 0;
+const LCDC_ENABLE: u8 = 0b10000000;
+const STAT_WRITABLE: u8 = 0b01111000;
+const STAT_UNUSED_BITS: u8 = 0b10000000;
+const STAT_LYC_EQUAL: u8 = 0b00000100;
+const STAT_HBLANK_SOURCE: u8 = 0b00001000;
+const STAT_VBLANK_SOURCE: u8 = 0b00010000;
+const STAT_OAM_SOURCE: u8 = 0b00100000;
+const STAT_LYC_SOURCE: u8 = 0b01000000;
 
-    if cycle % 456 == 0 {
-        let ly = mmu.get_word(LY_REGISTER as usize)? + 1;
-        if ly == 154 {
-            mmu.set_word(LY_REGISTER as usize, 0)?;
+const PALETTE_RAM_SIZE: usize = 64;
+const PALETTE_INDEX_MASK: u8 = 0b00111111;
+const PALETTE_AUTO_INCREMENT: u8 = 0b10000000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+/// CGB palette RAM accessed through an index register (BCPS/OCPS) and a
+/// data register (BCPD/OCPD).
+#[derive(Debug)]
+struct PaletteRam {
+    data: [u8; PALETTE_RAM_SIZE],
+    specification: u8,
+}
+
+impl PaletteRam {
+    fn new() -> Self {
+        PaletteRam {
+            data: [0; PALETTE_RAM_SIZE],
+            specification: 0,
+        }
+    }
+
+    fn get_specification(&self) -> u8 {
+        self.specification | !(PALETTE_INDEX_MASK | PALETTE_AUTO_INCREMENT)
+    }
+
+    fn set_specification(&mut self, value: u8) {
+        self.specification = value & (PALETTE_INDEX_MASK | PALETTE_AUTO_INCREMENT);
+    }
+
+    fn get_data(&self) -> u8 {
+        self.data[(self.specification & PALETTE_INDEX_MASK) as usize]
+    }
+
+    fn set_data(&mut self, value: u8) {
+        let index = self.specification & PALETTE_INDEX_MASK;
+        self.data[index as usize] = value;
+        if self.specification & PALETTE_AUTO_INCREMENT != 0 {
+            let index = (index + 1) & PALETTE_INDEX_MASK;
+            self.specification = PALETTE_AUTO_INCREMENT | index;
+        }
+    }
+}
+
+/// LCD timing and registers. Each line is 456 dots: OAM scan, drawing and
+/// HBlank on the 144 visible lines, then 10 lines of VBlank.
+#[derive(Debug)]
+pub struct PPU {
+    dots: u32,
+    ly: u8,
+    mode: Mode,
+    stat_line: bool,
+    lcdc: u8,
+    stat: u8,
+    scy: u8,
+    scx: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+    bg_palettes: PaletteRam,
+    obj_palettes: PaletteRam,
+    cgb_mode: bool,
+}
+
+impl PPU {
+    pub fn new(cgb_mode: bool) -> Self {
+        PPU {
+            dots: 0,
+            ly: 0,
+            mode: Mode::HBlank,
+            stat_line: false,
+            lcdc: 0,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            lyc: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            bg_palettes: PaletteRam::new(),
+            obj_palettes: PaletteRam::new(),
+            cgb_mode,
+        }
+    }
+
+    pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn ly(&self) -> u8 {
+        self.ly
+    }
+
+    /// Advances the PPU by the number of M-cycles the CPU just consumed.
+    pub fn tick(&mut self, cycles: u8, double_speed: bool, interrupts: &mut InterruptController) {
+        if self.lcdc & LCDC_ENABLE == 0 {
+            return;
+        }
+        let dots_per_m_cycle = match double_speed {
+            true => DOTS_PER_M_CYCLE / 2,
+            false => DOTS_PER_M_CYCLE,
+        };
+        for _ in 0..cycles {
+            self.dots += dots_per_m_cycle;
+            if self.dots >= DOTS_PER_LINE {
+                self.dots -= DOTS_PER_LINE;
+                self.ly = (self.ly + 1) % LINES_PER_FRAME;
+                if self.ly == VBLANK_LINE {
+                    interrupts.request(Interrupt::VBlank);
+                }
+            }
+            self.update_mode();
+            self.update_stat_line(interrupts);
+        }
+    }
+
+    pub fn read(&self, address: usize) -> u8 {
+        match address {
+            LCDC_REGISTER => self.lcdc,
+            STAT_REGISTER => {
+                let lyc_equal = if self.ly == self.lyc {
+                    STAT_LYC_EQUAL
+                } else {
+                    0
+                };
+                STAT_UNUSED_BITS | self.stat | lyc_equal | self.mode as u8
+            }
+            SCY_REGISTER => self.scy,
+            SCX_REGISTER => self.scx,
+            LY_REGISTER => self.ly,
+            LYC_REGISTER => self.lyc,
+            BGP_REGISTER => self.bgp,
+            OBP0_REGISTER => self.obp0,
+            OBP1_REGISTER => self.obp1,
+            WY_REGISTER => self.wy,
+            WX_REGISTER => self.wx,
+            BCPS_REGISTER if self.cgb_mode => self.bg_palettes.get_specification(),
+            BCPD_REGISTER if self.cgb_mode => self.bg_palettes.get_data(),
+            OCPS_REGISTER if self.cgb_mode => self.obj_palettes.get_specification(),
+            OCPD_REGISTER if self.cgb_mode => self.obj_palettes.get_data(),
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: usize, value: u8, interrupts: &mut InterruptController) {
+        match address {
+            LCDC_REGISTER => self.set_lcdc(value),
+            STAT_REGISTER => self.stat = value & STAT_WRITABLE,
+            SCY_REGISTER => self.scy = value,
+            SCX_REGISTER => self.scx = value,
+            LYC_REGISTER => self.lyc = value,
+            BGP_REGISTER => self.bgp = value,
+            OBP0_REGISTER => self.obp0 = value,
+            OBP1_REGISTER => self.obp1 = value,
+            WY_REGISTER => self.wy = value,
+            WX_REGISTER => self.wx = value,
+            BCPS_REGISTER if self.cgb_mode => self.bg_palettes.set_specification(value),
+            BCPD_REGISTER if self.cgb_mode => self.bg_palettes.set_data(value),
+            OCPS_REGISTER if self.cgb_mode => self.obj_palettes.set_specification(value),
+            OCPD_REGISTER if self.cgb_mode => self.obj_palettes.set_data(value),
+            // LY is read-only.
+            _ => return,
+        }
+        self.update_stat_line(interrupts);
+    }
+
+    /// Moves to the start of line `ly` without the side effects of the
+    /// normal timing, e.g. to reproduce the post-boot state.
+    pub fn set_line(&mut self, ly: u8) {
+        self.ly = ly % LINES_PER_FRAME;
+        self.dots = 0;
+        if self.lcdc & LCDC_ENABLE != 0 {
+            self.update_mode();
+        }
+    }
+
+    /// Turning the LCD off resets LY and leaves the PPU in mode 0; turning
+    /// it on restarts at the top of the frame.
+    fn set_lcdc(&mut self, value: u8) {
+        let enabled = self.lcdc & LCDC_ENABLE != 0;
+        self.lcdc = value;
+        match (enabled, value & LCDC_ENABLE != 0) {
+            (true, false) => {
+                self.ly = 0;
+                self.dots = 0;
+                self.mode = Mode::HBlank;
+            }
+            (false, true) => self.update_mode(),
+            _ => {}
+        }
+    }
+
+    fn update_mode(&mut self) {
+        self.mode = if self.ly >= VBLANK_LINE {
+            Mode::VBlank
+        } else if self.dots < OAM_SCAN_DOTS {
+            Mode::OamScan
+        } else if self.dots < OAM_SCAN_DOTS + DRAWING_DOTS {
+            Mode::Drawing
         } else {
-            mmu.set_word(LY_REGISTER as usize, ly)?;
+            Mode::HBlank
+        };
+    }
+
+    /// The STAT interrupt fires on the rising edge of the OR of all enabled
+    /// sources.
+    fn update_stat_line(&mut self, interrupts: &mut InterruptController) {
+        let enabled = self.lcdc & LCDC_ENABLE != 0;
+        let source = match self.mode {
+            Mode::HBlank => STAT_HBLANK_SOURCE,
+            Mode::VBlank => STAT_VBLANK_SOURCE,
+            Mode::OamScan => STAT_OAM_SOURCE,
+            Mode::Drawing => 0,
+        };
+        let line = enabled
+            && (self.stat & source != 0
+                || (self.stat & STAT_LYC_SOURCE != 0 && self.ly == self.lyc));
+        if line && !self.stat_line {
+            interrupts.request(Interrupt::Stat);
         }
-        cycle = 0;
+        self.stat_line = line;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const M_CYCLES_PER_LINE: u8 = (DOTS_PER_LINE / DOTS_PER_M_CYCLE) as u8;
+
+    #[test]
+    fn ly_ignores_writes() {
+        let mut interrupts = InterruptController::new();
+        let mut ppu = PPU::new(false);
+        ppu.write(LCDC_REGISTER, LCDC_ENABLE, &mut interrupts);
+        ppu.tick(M_CYCLES_PER_LINE, false, &mut interrupts);
+        assert_eq!(ppu.read(LY_REGISTER), 1);
+        ppu.write(LY_REGISTER, 0x42, &mut interrupts);
+        assert_eq!(ppu.read(LY_REGISTER), 1);
+    }
+
+    #[test]
+    fn stat_keeps_only_the_interrupt_sources() {
+        let mut interrupts = InterruptController::new();
+        let mut ppu = PPU::new(false);
+        ppu.write(LYC_REGISTER, 1, &mut interrupts);
+        ppu.write(STAT_REGISTER, 0xFF, &mut interrupts);
+        assert_eq!(ppu.read(STAT_REGISTER), 0xF8);
+        ppu.write(STAT_REGISTER, 0x00, &mut interrupts);
+        assert_eq!(ppu.read(STAT_REGISTER), 0x80);
+
+        // Mode and coincidence bits come from the PPU, not the write.
+        ppu.write(LYC_REGISTER, 0, &mut interrupts);
+        assert_eq!(ppu.read(STAT_REGISTER), 0x80 | STAT_LYC_EQUAL);
     }
-    cycle += 1;
-    Ok(())
 }
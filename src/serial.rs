@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+
+use crate::interrupts::{Interrupt, InterruptController};
+
+pub const SB_REGISTER: usize = 0xFF01;
+pub const SC_REGISTER: usize = 0xFF02;
+
+const SC_TRANSFER: u8 = 0b10000000;
+const SC_FAST_CLOCK: u8 = 0b00000010;
+const SC_INTERNAL_CLOCK: u8 = 0b00000001;
+
+/// M-cycles to shift out 8 bits with the 8192 Hz internal clock, or with
+/// the CGB 262144 Hz fast clock.
+const TRANSFER_CYCLES: u16 = 1024;
+const FAST_TRANSFER_CYCLES: u16 = 32;
+/// Bytes sent kept until read; the oldest are dropped beyond that.
+const OUTPUT_CAPACITY: usize = 0x10000;
+
+/// SB and SC with no link partner: transfers on the internal clock shift in
+/// 0xFF, and the bytes sent are kept so test ROM output can be read back.
+#[derive(Debug)]
+pub struct Serial {
+    data: u8,
+    control: u8,
+    remaining: u16,
+    output: VecDeque<u8>,
+    cgb_mode: bool,
+}
+
+impl Serial {
+    pub fn new(cgb_mode: bool) -> Self {
+        Serial {
+            data: 0,
+            control: 0,
+            remaining: 0,
+            output: VecDeque::new(),
+            cgb_mode,
+        }
+    }
+
+    pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+    }
+
+    pub fn tick(&mut self, cycles: u8, interrupts: &mut InterruptController) {
+        if self.remaining == 0 {
+            return;
+        }
+        self.remaining = self.remaining.saturating_sub(cycles as u16);
+        if self.remaining == 0 {
+            self.data = 0xFF;
+            self.control &= !SC_TRANSFER;
+            interrupts.request(Interrupt::Serial);
+        }
+    }
+
+    pub fn read(&self, address: usize) -> u8 {
+        match address {
+            SB_REGISTER => self.data,
+            SC_REGISTER => self.control | !self.writable_control(),
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: usize, value: u8) {
+        match address {
+            SB_REGISTER => self.data = value,
+            SC_REGISTER => {
+                self.control = value & self.writable_control();
+                if self.control & (SC_TRANSFER | SC_INTERNAL_CLOCK)
+                    == SC_TRANSFER | SC_INTERNAL_CLOCK
+                {
+                    if self.output.len() == OUTPUT_CAPACITY {
+                        self.output.pop_front();
+                    }
+                    self.output.push_back(self.data);
+                    self.remaining = match self.control & SC_FAST_CLOCK {
+                        0 => TRANSFER_CYCLES,
+                        _ => FAST_TRANSFER_CYCLES,
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Bytes sent since the last call, at most the last `OUTPUT_CAPACITY`.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.output.drain(..).collect()
+    }
+
+    fn writable_control(&self) -> u8 {
+        match self.cgb_mode {
+            true => SC_TRANSFER | SC_FAST_CLOCK | SC_INTERNAL_CLOCK,
+            false => SC_TRANSFER | SC_INTERNAL_CLOCK,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sc_unused_bits_read_as_1() {
+        let mut serial = Serial::new(false);
+        serial.write(SC_REGISTER, 0x00);
+        assert_eq!(serial.read(SC_REGISTER), 0x7E);
+
+        // The CGB fast clock bit is writable.
+        let mut serial = Serial::new(true);
+        serial.write(SC_REGISTER, 0x00);
+        assert_eq!(serial.read(SC_REGISTER), 0x7C);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::interrupts::{Interrupt, InterruptController};
+
+pub const DIV_REGISTER: usize = 0xFF04;
+pub const TIMA_REGISTER: usize = 0xFF05;
+pub const TMA_REGISTER: usize = 0xFF06;
+pub const TAC_REGISTER: usize = 0xFF07;
+
+const TAC_ENABLE: u8 = 0b100;
+const TAC_CLOCK_MASK: u8 = 0b011;
+const TAC_UNUSED_BITS: u8 = 0b11111000;
+
+/// The system counter advances by 4 every M-cycle; DIV is its high byte.
+const COUNTER_PER_M_CYCLE: u16 = 4;
+
+/// DIV, TIMA, TMA and TAC. TIMA increments on the falling edge of the
+/// system counter bit selected by TAC, so resetting DIV can tick it.
+#[derive(Debug, Default)]
+pub struct Timer {
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tick(&mut self, cycles: u8, interrupts: &mut InterruptController) {
+        for _ in 0..cycles {
+            let counter = self.counter.wrapping_add(COUNTER_PER_M_CYCLE);
+            self.set_counter(counter, interrupts);
+        }
+    }
+
+    pub fn read(&self, address: usize) -> u8 {
+        match address {
+            DIV_REGISTER => (self.counter >> 8) as u8,
+            TIMA_REGISTER => self.tima,
+            TMA_REGISTER => self.tma,
+            TAC_REGISTER => self.tac | TAC_UNUSED_BITS,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: usize, value: u8, interrupts: &mut InterruptController) {
+        match address {
+            DIV_REGISTER => self.set_counter(0, interrupts),
+            TIMA_REGISTER => self.tima = value,
+            TMA_REGISTER => self.tma = value,
+            TAC_REGISTER => {
+                let selected = self.selected_bit();
+                self.tac = value & !TAC_UNUSED_BITS;
+                // Disabling the timer or switching clocks is a falling edge
+                // too when the old bit was set and the new one isn't.
+                if selected && !self.selected_bit() {
+                    self.increment_tima(interrupts);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Sets the system counter without the side effects of writing DIV.
+    pub fn set_div(&mut self, div: u8) {
+        self.counter = (div as u16) << 8;
+    }
+
+    fn set_counter(&mut self, counter: u16, interrupts: &mut InterruptController) {
+        let selected = self.selected_bit();
+        self.counter = counter;
+        if selected && !self.selected_bit() {
+            self.increment_tima(interrupts);
+        }
+    }
+
+    /// State of the counter bit TIMA is clocked from, gated by TAC enable.
+    fn selected_bit(&self) -> bool {
+        let bit = match self.tac & TAC_CLOCK_MASK {
+            0b00 => 9,
+            0b01 => 3,
+            0b10 => 5,
+            _ => 7,
+        };
+        self.tac & TAC_ENABLE != 0 && self.counter & (1 << bit) != 0
+    }
+
+    fn increment_tima(&mut self, interrupts: &mut InterruptController) {
+        let (tima, overflow) = self.tima.overflowing_add(1);
+        if overflow {
+            self.tima = self.tma;
+            interrupts.request(Interrupt::Timer);
+        } else {
+            self.tima = tima;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Enabled, clocked from counter bit 3 (every 4 M-cycles).
+    const TAC_FAST: u8 = TAC_ENABLE | 0b01;
+
+    #[test]
+    fn writing_div_resets_it() {
+        let mut interrupts = InterruptController::new();
+        let mut timer = Timer::new();
+        timer.tick(u8::MAX, &mut interrupts);
+        timer.tick(1, &mut interrupts);
+        assert_eq!(timer.read(DIV_REGISTER), 0x04);
+        timer.write(DIV_REGISTER, 0x5A, &mut interrupts);
+        assert_eq!(timer.read(DIV_REGISTER), 0x00);
+    }
+
+    #[test]
+    fn resetting_div_on_the_selected_bit_ticks_tima() {
+        let mut interrupts = InterruptController::new();
+        let mut timer = Timer::new();
+        timer.write(TAC_REGISTER, TAC_FAST, &mut interrupts);
+        timer.tick(1, &mut interrupts);
+        timer.write(DIV_REGISTER, 0, &mut interrupts);
+        assert_eq!(timer.read(TIMA_REGISTER), 0);
+
+        timer.tick(2, &mut interrupts);
+        timer.write(DIV_REGISTER, 0, &mut interrupts);
+        assert_eq!(timer.read(TIMA_REGISTER), 1);
+    }
+
+    #[test]
+    fn disabling_on_the_selected_bit_ticks_tima() {
+        let mut interrupts = InterruptController::new();
+        let mut timer = Timer::new();
+        timer.write(TAC_REGISTER, TAC_FAST, &mut interrupts);
+        timer.tick(2, &mut interrupts);
+        timer.write(TAC_REGISTER, 0, &mut interrupts);
+        assert_eq!(timer.read(TIMA_REGISTER), 1);
+    }
+
+    #[test]
+    fn tac_unused_bits_read_as_1() {
+        let mut interrupts = InterruptController::new();
+        let mut timer = Timer::new();
+        timer.write(TAC_REGISTER, 0x00, &mut interrupts);
+        assert_eq!(timer.read(TAC_REGISTER), 0xF8);
+        timer.write(TAC_REGISTER, 0xFF, &mut interrupts);
+        assert_eq!(timer.read(TAC_REGISTER), 0xFF);
+    }
+}
@@ -0,0 +1,87 @@
+//! MBC3 real-time clock behaviour across sessions, driven by injected
+//! clocks so results don't depend on when the tests run.
+
+use gbmu::cartridge::mbc3::MBC3;
+use gbmu::cartridge::rtc::{
+    Rtc, DAY_HIGH_REGISTER, DAY_LOW_REGISTER, HOURS_REGISTER, MINUTES_REGISTER, SECONDS_REGISTER,
+};
+use gbmu::cartridge::MemoryBankController;
+use gbmu::clock::{Clock, FixedClock, SteppedClock};
+
+const ROM_SIZE: usize = 0x8000;
+const RAM_SIZE: usize = 0x2000;
+const START_TIME: u64 = 1_700_000_000;
+/// The RTC footer after RAM ends with the save time as a 64-bit UNIX
+/// timestamp.
+const TIMESTAMP_OFFSET: usize = RAM_SIZE + 40;
+
+fn mbc3(clock: impl Clock + 'static) -> MBC3 {
+    let rtc = Some(Rtc::new(Box::new(clock)));
+    let mut mbc = MBC3::new(vec![0; ROM_SIZE], ROM_SIZE, RAM_SIZE, rtc);
+    mbc.rom_write(0x0000, 0x0A);
+    mbc
+}
+
+fn latch(mbc: &mut MBC3) {
+    mbc.rom_write(0x6000, 0x00);
+    mbc.rom_write(0x6000, 0x01);
+}
+
+fn read_rtc(mbc: &mut MBC3, register: u8) -> u8 {
+    mbc.rom_write(0x4000, register);
+    mbc.ram_read(0)
+}
+
+fn write_rtc(mbc: &mut MBC3, register: u8, value: u8) {
+    mbc.rom_write(0x4000, register);
+    mbc.ram_write(0, value);
+}
+
+#[test]
+fn catches_up_on_time_between_sessions() {
+    let clock = SteppedClock::new(START_TIME);
+    let mut mbc = mbc3(clock.clone());
+    write_rtc(&mut mbc, HOURS_REGISTER, 23);
+    let save = mbc.save_data();
+
+    clock.advance(2 * 86_400 + 3_600 + 61);
+    let mut mbc = mbc3(clock);
+    mbc.load_save_data(&save);
+    latch(&mut mbc);
+
+    assert_eq!(read_rtc(&mut mbc, SECONDS_REGISTER), 1);
+    assert_eq!(read_rtc(&mut mbc, MINUTES_REGISTER), 1);
+    assert_eq!(read_rtc(&mut mbc, HOURS_REGISTER), 0);
+    assert_eq!(read_rtc(&mut mbc, DAY_LOW_REGISTER), 3);
+}
+
+#[test]
+fn halted_clock_ignores_elapsed_time() {
+    let clock = SteppedClock::new(START_TIME);
+    let mut mbc = mbc3(clock.clone());
+    write_rtc(&mut mbc, DAY_HIGH_REGISTER, 0x40);
+    let save = mbc.save_data();
+
+    clock.advance(86_400);
+    let mut mbc = mbc3(clock);
+    mbc.load_save_data(&save);
+    latch(&mut mbc);
+
+    assert_eq!(read_rtc(&mut mbc, DAY_LOW_REGISTER), 0);
+    assert_eq!(read_rtc(&mut mbc, DAY_HIGH_REGISTER), 0x40);
+}
+
+#[test]
+fn fixed_clock_saves_are_reproducible() {
+    let run = || {
+        let mut mbc = mbc3(FixedClock::new(START_TIME));
+        for _ in 0..0x10000 {
+            mbc.tick(u8::MAX, false);
+        }
+        mbc.save_data()
+    };
+    let save = run();
+    let timestamp = &save[TIMESTAMP_OFFSET..TIMESTAMP_OFFSET + 8];
+    assert_eq!(timestamp, START_TIME.to_le_bytes());
+    assert_eq!(save, run());
+}
@@ -0,0 +1,366 @@
+//! Per-instruction conformance tests in the SingleStepTests sm83 format.
+//!
+//! A few hand-made cases in the same format, covering ALU operations, are
+//! vendored in `tests/sm83/smoke` and always run. The full suite isn't
+//! vendored, so its test is ignored by default: point `SM83_TESTS_DIR` at a
+//! directory holding the `xx.json` / `cb xx.json` files (default
+//! `tests/sm83/v1`) and run `cargo test -- --ignored`. Each case is run on
+//! a flat 64 KiB bus and every mismatching register, flag, memory byte, bus
+//! access and cycle count is reported per opcode.
+//!
+//! The CPU only reports its cycles once per instruction, so bus accesses
+//! are compared in order against the non-idle cycles of the fixture, and
+//! idle cycles through the total count.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use gbmu::bus::{Bus, IE_REGISTER, IF_REGISTER};
+use gbmu::cpu::CPU;
+use gbmu::error::EmuError;
+use gbmu::interrupts::{self, Interrupt};
+use serde_json::Value;
+
+const DEFAULT_TESTS_DIR: &str = "tests/sm83/v1";
+const SMOKE_TESTS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/sm83/smoke");
+const MEMORY_SIZE: usize = 0x10000;
+const REPORTED_FAILURES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Access {
+    address: u16,
+    value: u8,
+    kind: Kind,
+}
+
+impl Access {
+    /// A fixture cycle, `[address, value, "r-m"]`; `None` when the bus is
+    /// idle.
+    fn from_json(json: &Value) -> Option<Self> {
+        let cycle = json.as_array()?;
+        let pins = cycle.get(2)?.as_str()?;
+        let kind = match (pins.contains('r'), pins.contains('w')) {
+            (true, _) => Kind::Read,
+            (false, true) => Kind::Write,
+            (false, false) => return None,
+        };
+        Some(Access {
+            address: cycle.first()?.as_u64()? as u16,
+            value: cycle.get(1)?.as_u64()? as u8,
+            kind,
+        })
+    }
+}
+
+impl std::fmt::Display for Access {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let kind = match self.kind {
+            Kind::Read => 'r',
+            Kind::Write => 'w',
+        };
+        write!(f, "{kind} {:#06x}={:#04x}", self.address, self.value)
+    }
+}
+
+/// Flat memory recording every CPU access. Interrupt state is read
+/// directly so polling IF and IE doesn't show up as bus activity.
+struct FlatBus {
+    memory: Box<[u8]>,
+    accesses: Vec<Access>,
+}
+
+impl FlatBus {
+    fn new() -> Self {
+        Self {
+            memory: vec![0; MEMORY_SIZE].into_boxed_slice(),
+            accesses: Vec::new(),
+        }
+    }
+}
+
+impl Bus for FlatBus {
+    fn get_word(&mut self, address: usize) -> Result<u8, EmuError> {
+        let value = self.memory[address % MEMORY_SIZE];
+        self.accesses.push(Access {
+            address: address as u16,
+            value,
+            kind: Kind::Read,
+        });
+        Ok(value)
+    }
+
+    fn set_word(&mut self, address: usize, value: u8) -> Result<(), EmuError> {
+        self.memory[address % MEMORY_SIZE] = value;
+        self.accesses.push(Access {
+            address: address as u16,
+            value,
+            kind: Kind::Write,
+        });
+        Ok(())
+    }
+
+    fn pending_interrupt(&mut self) -> Result<Option<Interrupt>, EmuError> {
+        let pending = self.memory[IF_REGISTER] & self.memory[IE_REGISTER];
+        Ok(interrupts::highest_priority(pending))
+    }
+
+    fn interrupt_requested(&mut self, interrupt: Interrupt) -> Result<bool, EmuError> {
+        Ok(self.memory[IF_REGISTER] & interrupt.mask() != 0)
+    }
+
+    fn acknowledge_interrupt(&mut self, interrupt: Interrupt) -> Result<(), EmuError> {
+        self.memory[IF_REGISTER] &= !interrupt.mask();
+        Ok(())
+    }
+}
+
+struct State {
+    pc: u16,
+    sp: u16,
+    af: u16,
+    bc: u16,
+    de: u16,
+    hl: u16,
+    ime: bool,
+    ie: Option<u8>,
+    ram: Vec<(u16, u8)>,
+}
+
+impl State {
+    fn from_json(json: &Value) -> Result<Self, String> {
+        let field = |name: &str| {
+            json.get(name)
+                .and_then(Value::as_u64)
+                .ok_or_else(|| format!("missing field '{}'", name))
+        };
+        let pair = |high: &str, low: &str| -> Result<u16, String> {
+            Ok((field(high)? << 8 | field(low)?) as u16)
+        };
+        let ram = json
+            .get("ram")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let entry = entry.as_array()?;
+                let address = entry.first()?.as_u64()? as u16;
+                let value = entry.get(1)?.as_u64()? as u8;
+                Some((address, value))
+            })
+            .collect();
+        Ok(State {
+            pc: field("pc")? as u16,
+            sp: field("sp")? as u16,
+            af: pair("a", "f")?,
+            bc: pair("b", "c")?,
+            de: pair("d", "e")?,
+            hl: pair("h", "l")?,
+            ime: field("ime").unwrap_or_default() != 0,
+            ie: field("ie").ok().map(|ie| ie as u8),
+            ram,
+        })
+    }
+}
+
+/// First opcode byte of the instruction named by a case (`"8e 0042"`).
+fn opcode_of(name: &str) -> Option<u8> {
+    u8::from_str_radix(name.split_whitespace().next()?, 16).ok()
+}
+
+fn run_case(case: &Value) -> Result<Vec<String>, String> {
+    let name = case.get("name").and_then(Value::as_str);
+    let initial = State::from_json(case.get("initial").ok_or("missing initial state")?)?;
+    let expected = State::from_json(case.get("final").ok_or("missing final state")?)?;
+    let expected_cycles = case
+        .get("cycles")
+        .and_then(Value::as_array)
+        .map_or(&[][..], Vec::as_slice);
+
+    let mut bus = FlatBus::new();
+    for &(address, value) in &initial.ram {
+        bus.memory[address as usize] = value;
+    }
+    if let Some(ie) = initial.ie {
+        bus.memory[0xFFFF] = ie;
+    }
+
+    // The generator may leave the opcode already fetched, with PC one past
+    // it. In that case the final PC also accounts for the next prefetch.
+    let prefetched = match name.and_then(opcode_of) {
+        Some(opcode) => {
+            bus.memory[initial.pc as usize] != opcode
+                && bus.memory[initial.pc.wrapping_sub(1) as usize] == opcode
+        }
+        None => false,
+    };
+    let pc_adjust = prefetched as u16;
+
+    let mut cpu = CPU::new();
+    cpu.registers.pc = initial.pc.wrapping_sub(pc_adjust);
+    cpu.registers.sp = initial.sp;
+    cpu.registers.set_af(initial.af);
+    cpu.registers.set_bc(initial.bc);
+    cpu.registers.set_de(initial.de);
+    cpu.registers.set_hl(initial.hl);
+    cpu.set_ime(initial.ime);
+
+    let cycles = cpu.run(&mut bus).map_err(|error| error.to_string())?;
+
+    let mut mismatches = Vec::new();
+    let mut compare = |what: &str, expected: u64, actual: u64| {
+        if expected != actual {
+            mismatches.push(format!(
+                "{what}: expected {expected:#06x}, got {actual:#06x}"
+            ));
+        }
+    };
+    let registers = &cpu.registers;
+    compare(
+        "pc",
+        expected.pc as u64,
+        registers.pc.wrapping_add(pc_adjust) as u64,
+    );
+    compare("sp", expected.sp as u64, registers.sp as u64);
+    compare(
+        "a",
+        (expected.af >> 8) as u64,
+        (registers.get_af() >> 8) as u64,
+    );
+    compare(
+        "f",
+        (expected.af & 0xFF) as u64,
+        (registers.get_af() & 0xFF) as u64,
+    );
+    compare("bc", expected.bc as u64, registers.get_bc() as u64);
+    compare("de", expected.de as u64, registers.get_de() as u64);
+    compare("hl", expected.hl as u64, registers.get_hl() as u64);
+    compare("ime", expected.ime as u64, cpu.ime() as u64);
+    if let Some(ie) = expected.ie {
+        compare("ie", ie as u64, bus.memory[0xFFFF] as u64);
+    }
+    for &(address, value) in &expected.ram {
+        compare(
+            &format!("[{address:#06x}]"),
+            value as u64,
+            bus.memory[address as usize] as u64,
+        );
+    }
+    compare("cycles", expected_cycles.len() as u64, cycles as u64);
+
+    // With a prefetch, the opcode fetch happened before the case and the
+    // fixture ends on the fetch of the next one instead.
+    let mut expected_accesses: Vec<Access> = expected_cycles
+        .iter()
+        .filter_map(Access::from_json)
+        .collect();
+    let mut accesses = &bus.accesses[..];
+    if prefetched {
+        accesses = accesses.get(1..).unwrap_or_default();
+        if expected_cycles.last().and_then(Access::from_json).is_some() {
+            expected_accesses.pop();
+        }
+    }
+    let length = expected_accesses.len().max(accesses.len());
+    for index in 0..length {
+        let expected = expected_accesses.get(index);
+        let actual = accesses.get(index);
+        if expected != actual {
+            let show =
+                |access: Option<&Access>| access.map_or("none".to_string(), Access::to_string);
+            mismatches.push(format!(
+                "access {index}: expected {}, got {}",
+                show(expected),
+                show(actual)
+            ));
+            break;
+        }
+    }
+    Ok(mismatches)
+}
+
+fn fixture_files(directory: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(directory)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Runs every fixture in `directory`, failing with the opcodes that
+/// mismatched.
+fn run_fixtures(directory: &str) {
+    let files = fixture_files(Path::new(directory));
+    assert!(
+        !files.is_empty(),
+        "sm83: no fixtures in '{directory}', set SM83_TESTS_DIR to run them"
+    );
+
+    let mut failed_opcodes = Vec::new();
+    for file in &files {
+        let opcode = file.file_stem().unwrap_or_default().to_string_lossy();
+        let text = fs::read_to_string(file).expect("unreadable fixture");
+        let cases: Value =
+            serde_json::from_str(&text).unwrap_or_else(|error| panic!("{opcode}: {error}"));
+        let cases = cases.as_array().map_or(&[][..], Vec::as_slice);
+
+        let mut failures = Vec::new();
+        for case in cases {
+            let name = case
+                .get("name")
+                .and_then(Value::as_str)
+                .map_or_else(|| opcode.to_string(), str::to_string);
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| run_case(case)))
+                .unwrap_or_else(|_| Err("panicked".to_string()));
+            match outcome {
+                Ok(mismatches) if mismatches.is_empty() => {}
+                Ok(mismatches) => failures.push(format!("{name}: {}", mismatches.join(", "))),
+                Err(error) => failures.push(format!("{name}: {error}")),
+            }
+        }
+
+        if !failures.is_empty() {
+            eprintln!(
+                "sm83 {opcode}: {}/{} cases failed",
+                failures.len(),
+                cases.len()
+            );
+            for failure in failures.iter().take(REPORTED_FAILURES) {
+                eprintln!("    {failure}");
+            }
+            failed_opcodes.push(opcode.into_owned());
+        }
+    }
+
+    assert!(
+        failed_opcodes.is_empty(),
+        "{} of {} opcodes failed: {}",
+        failed_opcodes.len(),
+        files.len(),
+        failed_opcodes.join(" ")
+    );
+}
+
+#[test]
+fn sm83_smoke_tests() {
+    run_fixtures(SMOKE_TESTS_DIR);
+}
+
+#[test]
+#[ignore = "needs the SingleStepTests fixtures, see SM83_TESTS_DIR"]
+fn sm83_single_step_tests() {
+    let directory = env::var("SM83_TESTS_DIR").unwrap_or_else(|_| DEFAULT_TESTS_DIR.to_string());
+    run_fixtures(&directory);
+}